@@ -4,6 +4,9 @@ db.books.createIndex({title: 1, author: 1}, {unique: true});
 db.books.createIndex({author: 1, year: -1});
 db.books.createIndex({title: 1});
 db.books.createIndex({year: -1});
+db.books.createIndex({quantity: 1});
+db.books.createIndex({last_modified: -1});
+db.books.createIndex({title: 'text', author: 'text'}, {name: 'title_text_author_text', weights: {title: 10, author: 5}, default_language: 'english'});
 var now = new Date();
 db.books.insertMany([
     {
@@ -13,6 +16,7 @@ db.books.insertMany([
         'quantity': NumberInt(1),
         'first_created': now,
         'last_modified': now,        
+        'version': NumberLong(1),
     },
     {
         'title': 'When Worlds Collide',
@@ -21,6 +25,7 @@ db.books.insertMany([
         'quantity': NumberInt(1),
         'first_created': now,
         'last_modified': now,        
+        'version': NumberLong(1),
     },
     {
         'title': 'Earth Abides',
@@ -29,6 +34,7 @@ db.books.insertMany([
         'quantity': NumberInt(1),
         'first_created': now,
         'last_modified': now,        
+        'version': NumberLong(1),
     },
     {
         'title': 'The Day of the Triffids',
@@ -37,6 +43,7 @@ db.books.insertMany([
         'quantity': NumberInt(1),
         'first_created': now,
         'last_modified': now,        
+        'version': NumberLong(1),
     },
     {
         'title': 'The Kraken Wakes',
@@ -45,6 +52,7 @@ db.books.insertMany([
         'quantity': NumberInt(1),
         'first_created': now,
         'last_modified': now,        
+        'version': NumberLong(1),
     },
     {
         'title': 'The Chrysalids',
@@ -53,6 +61,7 @@ db.books.insertMany([
         'quantity': NumberInt(1),
         'first_created': now,
         'last_modified': now,        
+        'version': NumberLong(1),
     },
     {
         'title': 'I Am Legend',
@@ -61,6 +70,7 @@ db.books.insertMany([
         'quantity': NumberInt(1),
         'first_created': now,
         'last_modified': now,        
+        'version': NumberLong(1),
     },
     {
         'title': 'The Long Tomorrow',
@@ -69,6 +79,7 @@ db.books.insertMany([
         'quantity': NumberInt(1),
         'first_created': now,
         'last_modified': now,        
+        'version': NumberLong(1),
     },
     {
         'title': 'The Death of Grass',
@@ -77,6 +88,7 @@ db.books.insertMany([
         'quantity': NumberInt(1),
         'first_created': now,
         'last_modified': now,        
+        'version': NumberLong(1),
     },
     {
         'title': 'The World in Winter',
@@ -85,6 +97,7 @@ db.books.insertMany([
         'quantity': NumberInt(1),
         'first_created': now,
         'last_modified': now,        
+        'version': NumberLong(1),
     },
     {
         'title': 'A Wrinkle in the Skin',
@@ -93,6 +106,7 @@ db.books.insertMany([
         'quantity': NumberInt(1),
         'first_created': now,
         'last_modified': now,        
+        'version': NumberLong(1),
     },
     {
         'title': 'Tripods Trilogy',
@@ -101,6 +115,7 @@ db.books.insertMany([
         'quantity': NumberInt(1),
         'first_created': now,
         'last_modified': now,        
+        'version': NumberLong(1),
     },
     {
         'title': 'On the Beach',
@@ -109,6 +124,7 @@ db.books.insertMany([
         'quantity': NumberInt(1),
         'first_created': now,
         'last_modified': now,        
+        'version': NumberLong(1),
     },
     {
         'title': 'The Black Cloud',
@@ -117,6 +133,7 @@ db.books.insertMany([
         'quantity': NumberInt(1),
         'first_created': now,
         'last_modified': now,        
+        'version': NumberLong(1),
     },
     {
         'title': 'Level 7',
@@ -125,6 +142,7 @@ db.books.insertMany([
         'quantity': NumberInt(1),
         'first_created': now,
         'last_modified': now,        
+        'version': NumberLong(1),
     },
     {
         'title': 'Alas, Babylon',
@@ -133,6 +151,7 @@ db.books.insertMany([
         'quantity': NumberInt(1),
         'first_created': now,
         'last_modified': now,        
+        'version': NumberLong(1),
     },
     {
         'title': 'A Canticle for Leibowitz',
@@ -141,6 +160,7 @@ db.books.insertMany([
         'quantity': NumberInt(1),
         'first_created': now,
         'last_modified': now,        
+        'version': NumberLong(1),
     },
     {
         'title': 'Hothouse',
@@ -149,6 +169,7 @@ db.books.insertMany([
         'quantity': NumberInt(1),
         'first_created': now,
         'last_modified': now,        
+        'version': NumberLong(1),
     },
     {
         'title': 'Some Will Not Die',
@@ -157,6 +178,7 @@ db.books.insertMany([
         'quantity': NumberInt(1),
         'first_created': now,
         'last_modified': now,        
+        'version': NumberLong(1),
     },
     {
         'title': 'The Drowned World',
@@ -165,42 +187,43 @@ db.books.insertMany([
         'quantity': NumberInt(1),
         'first_created': now,
         'last_modified': now,        
+        'version': NumberLong(1),
     },
     {
-        'id': '21',
         'title': "Cat's Cradle",
         'author': 'Kurt Vonnegut',
         'year': NumberInt(1963),
         'quantity': NumberInt(1),
         'first_created': now,
         'last_modified': now,        
+        'version': NumberLong(1),
     },
     {
-        'id': '22',
         'title': 'The Sheep Look Up',
         'author': 'John Brunner',
         'year': NumberInt(1972),
         'quantity': NumberInt(1),
         'first_created': now,
         'last_modified': now,        
+        'version': NumberLong(1),
     },
     {
-        'id': '23',
         'title': "Lucifer's Hammer",
         'author': 'Larry Niven and Jerry Pournelle',
         'year': NumberInt(1977),
         'quantity': NumberInt(1),
         'first_created': now,
         'last_modified': now,        
+        'version': NumberLong(1),
     },
     {
-        'id': '24',
         'title': 'The Stand',
         'author': 'Stephen King',
         'year': NumberInt(1978),
         'quantity': NumberInt(1),
         'first_created': now,
         'last_modified': now,        
+        'version': NumberLong(1),
     },
     {
         'title': 'Engine Summer',
@@ -215,147 +238,147 @@ db.books.insertMany([
         'quantity': NumberInt(1),
         'first_created': now,
         'last_modified': now,        
+        'version': NumberLong(1),
     },
     {
-        'id': '27',
         'title': 'Riddley Walker',
         'author': 'Russell Hoban',
         'year': NumberInt(1980),
         'quantity': NumberInt(1),
         'first_created': now,
         'last_modified': now,        
+        'version': NumberLong(1),
     },
     {
-        'id': '28',
         'title': 'Emergence',
         'author': 'David R. Palmer',
         'year': NumberInt(1984),
         'quantity': NumberInt(1),
         'first_created': now,
         'last_modified': now,        
+        'version': NumberLong(1),
     },
     {
-        'id': '29',
         'title': 'The Postman',
         'author': 'David Brin',
         'year': NumberInt(1985),
         'quantity': NumberInt(1),
         'first_created': now,
         'last_modified': now,        
+        'version': NumberLong(1),
     },
     {
-        'id': '30',
         'title': 'This Is the Way the World Ends',
         'author': 'James K. Morrow',
         'year': NumberInt(1985),
         'quantity': NumberInt(1),
     },    
     {
-        'id': '31',
         'title': 'Swan Song',
         'author': 'Robert R. McCammon',
         'year': NumberInt(1987),
         'quantity': NumberInt(1),
         'first_created': now,
         'last_modified': now,        
+        'version': NumberLong(1),
     },
     {
-        'id': '32',
         'title': 'The Children of Men',
         'author': 'P. D. James',
         'year': NumberInt(1992),
         'quantity': NumberInt(1),
         'first_created': now,
         'last_modified': now,        
+        'version': NumberLong(1),
     },
     {
-        'id': '33',
         'title': 'The Ice People',
         'author': 'Maggie Gee',
         'year': NumberInt(1998),
         'quantity': NumberInt(1),
         'first_created': now,
         'last_modified': now,        
+        'version': NumberLong(1),
     },
     {
-        'id': '34',
         'title': 'Dies the Fire',
         'author': 'S. M. Stirling',
         'year': NumberInt(2004),
         'quantity': NumberInt(1),
         'first_created': now,
         'last_modified': now,        
+        'version': NumberLong(1),
     },
     {
-        'id': '35',
         'title': 'The Road',
         'author': 'Cormac McCarthy',
         'year': NumberInt(2006),
         'quantity': NumberInt(1),
         'first_created': now,
         'last_modified': now,        
+        'version': NumberLong(1),
     },
     {
-        'id': '36',
         'title': 'The Year of the Flood',
         'author': 'Margaret Atwood',
         'year': NumberInt(2009),
         'quantity': NumberInt(1),
         'first_created': now,
         'last_modified': now,        
+        'version': NumberLong(1),
     },
     {
-        'id': '37',
         'title': 'One Second After',
         'author': 'William R. Forstchen',
         'year': NumberInt(2009),
         'quantity': NumberInt(1),
         'first_created': now,
         'last_modified': now,        
+        'version': NumberLong(1),
     },
     {
-        'id': '38',
         'title': 'Far North',
         'author': 'Marcel Theroux',
         'year': NumberInt(2009),
         'quantity': NumberInt(1),
         'first_created': now,
         'last_modified': now,        
+        'version': NumberLong(1),
     },
     {
-        'id': '39',
         'title': 'Wool',
         'author': 'Hugh Howey',
         'year': NumberInt(2011),
         'quantity': NumberInt(1),
         'first_created': now,
         'last_modified': now,        
+        'version': NumberLong(1),
     },
     {
-        'id': '40',
         'title': 'Shift',
         'author': 'Hugh Howey',
         'year': NumberInt(2013),
         'quantity': NumberInt(1),
         'first_created': now,
         'last_modified': now,        
+        'version': NumberLong(1),
     },
     {
-        'id': '41',
         'title': 'Dust',
         'author': 'Hugh Howey',
         'year': NumberInt(2014),
         'quantity': NumberInt(1),
         'first_created': now,
         'last_modified': now,        
+        'version': NumberLong(1),
     },
     {
-        'id': '42',
         'title': 'Station Eleven',
         'author': 'Emily St. John Mandel',
         'year': NumberInt(2014),
         'quantity': NumberInt(1),
         'first_created': now,
         'last_modified': now,        
+        'version': NumberLong(1),
     },
 ]);
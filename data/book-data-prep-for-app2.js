@@ -14,23 +14,26 @@ var now = new Date();
 db.books.updateOne(
     {"title": "The Last Man", "author": "Mary Shelley"},
     {"$push": {scores: {reference: "The Book Club", rating: NumberInt("7")}},
-     "$set": {'last_modified': now}}
+     "$set": {'last_modified': now},
+     "$inc": {'version': NumberLong(1)}}
 )
 
 db.books.updateOne(
     {"title": "The Last Man", "author": "Mary Shelley"},
     {"$push": {scores: {reference: "The Good Read", rating: NumberInt("6")}},
-     "$set": {'last_modified': now}}
+     "$set": {'last_modified': now},
+     "$inc": {'version': NumberLong(1)}}
 )
 
 db.books.updateOne(
     {"title": "When Worlds Collide", "author": "Philip Wylie & Edwin Balmer"},
-    {"$set": {scores: [], 'last_modified': now}}
+    {"$set": {scores: [], 'last_modified': now}, "$inc": {'version': NumberLong(1)}}
 )
 
 db.books.updateOne(
     {"title": "Earth Abides", "author": "George R. Stewart"},
     {"$push": {scores: {reference: "The Good Read", rating: NumberInt("6")}},
-     "$set": {'last_modified': now}}    
+     "$set": {'last_modified': now},
+     "$inc": {'version': NumberLong(1)}}    
 )
 
@@ -0,0 +1,32 @@
+use bson::doc;
+use std::error::Error;
+
+use crate::app1::app1_ensure_indexes;
+use crate::store::{StoreConnection, StoreLocation};
+
+const ADMIN_DB_NAME: &str = "admin";
+const ADMIN_APP_NAME: &str = "fluidity-demo-admin";
+
+// Check the MongoDB deployment holding the books store can be reached, by running a ping command
+//
+pub async fn admin_ping(location: &StoreLocation) -> Result<(), Box<dyn Error + Send + Sync>> {
+    match location.connect(ADMIN_APP_NAME)? {
+        StoreConnection::InMemory(_) => println!("Nothing to ping when using {}", location),
+        StoreConnection::MongoDb { client, .. } => {
+            client.database(ADMIN_DB_NAME).run_command(doc! {"ping": 1}, None).await?;
+            println!("Successfully pinged the MongoDB deployment of {}", location);
+        }
+    }
+
+    Ok(())
+}
+
+// Create the indexes app1 relies on in the books store, ahead of running app1
+//
+pub async fn admin_ensure_indexes(
+    location: &StoreLocation,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    app1_ensure_indexes(&location.connect(ADMIN_APP_NAME)?).await?;
+    println!("Ensured the indexes app1 relies on exist in {}", location);
+    Ok(())
+}
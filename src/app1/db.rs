@@ -1,19 +1,42 @@
-use bson::DateTime;
-use futures::prelude::*;
+use async_trait::async_trait;
+use bson::{oid::ObjectId, Bson, DateTime, Document};
+use futures::{
+    prelude::*,
+    stream::{self, BoxStream},
+};
 use mongodb::{
     bson::doc,
-    options::FindOptions,
-    {Client, Collection},
+    options::{CollectionOptions, CountOptions, FindOneOptions, FindOptions, IndexOptions},
+    {Client, ClientSession, Collection, IndexModel},
 };
 use serde::{Deserialize, Serialize};
-use std::error::Error;
 
-const DB_NAME: &str = "library";
-const COLL_NAME: &str = "books";
+use super::filter::{BooksFilter, TextMatch};
+use super::paging::{PageCursor, PageRequest};
+use super::sorting::{SortField, SortOrder};
+use crate::causal::CausalContext;
+use crate::error::AppError;
+use crate::fields::FieldSelection;
+use crate::store::{BookKey, OperationSettings, WriteCounts};
+
+// Fields of the shared book records which app1 owns and returns to clients
+pub const OWNED_FIELDS: &[&str] =
+    &["title", "author", "year", "quantity", "explicit", "first_created", "last_modified"];
+
+// Fields of a book which clients can change, as opposed to the timestamps managed by the DB tier
+const EDITABLE_FIELDS: &[&str] = &["title", "author", "year", "quantity", "explicit"];
+
+const TEXT_INDEX_NAME: &str = "title_text_author_text";
+const TEXT_SCORE_FIELD: &str = "text_score";
+
+// Stream of books read from the DB tier one at a time as the stream is consumed
+pub type BookStream = BoxStream<'static, Result<Book, AppError>>;
 
 // Book record
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Book {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub title: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -28,108 +51,663 @@ pub struct Book {
     pub first_created: Option<DateTime>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub last_modified: Option<DateTime>,
+    // Count of writes to the record by either app, identifying its version
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<i64>,
+}
+
+// Page of books returned from a listing, with the cursor to start the next page from (if there are
+// more books) and the total number of books matching the listing's filter (if requested)
+#[derive(Debug, Clone, Default)]
+pub struct BooksPage {
+    pub books: Vec<Book>,
+    pub next: Option<PageCursor>,
+    pub total: Option<u64>,
+}
+
+impl BooksPage {
+    // Build page from the books found, where one more book than the page's limit is requested from
+    // the DB tier, so that if that extra book is present, it's known another page follows
+    //
+    pub fn from_books(
+        mut books: Vec<Book>, sort: &SortOrder, page: &PageRequest, total: Option<u64>,
+    ) -> Result<Self, AppError> {
+        let next = match page.limit {
+            Some(limit) if books.len() > limit as usize => {
+                books.truncate(limit as usize);
+                books.last().map(|book| page_cursor_after(book, sort)).transpose()?
+            }
+            _ => None,
+        };
+        Ok(Self { books, next, total })
+    }
+}
+
+// Book found by a text search, with its relevance to the search text, where higher is more relevant
+#[derive(Debug, Clone)]
+pub struct BookSearchResult {
+    pub book: Book,
+    pub relevance: f64,
+}
+
+// Storage operations for the books inventory, which the REST API handlers depend on so they can
+// run against either MongoDB or the in-memory store, where each operation observes & advances the
+// causal context of the request it's part of
+#[async_trait]
+pub trait BooksStore: Send + Sync {
+    // Query books returning a page of books in the sort order, which match the filter, where only
+    // the selected fields of each book need be read
+    async fn db_find_books(
+        &self, filter: &BooksFilter, fields: &FieldSelection, sort: &SortOrder, page: &PageRequest,
+        causal: &CausalContext,
+    ) -> Result<BooksPage, AppError>;
+
+    // Query books returning a stream of all books in the sort order, which match the filter, where
+    // only the selected fields of each book need be read
+    async fn db_stream_books(
+        &self, filter: &BooksFilter, fields: &FieldSelection, sort: &SortOrder,
+        causal: &CausalContext,
+    ) -> Result<BookStream, AppError>;
+
+    // Search books' titles & authors for any of the words in the text, returning up to the limit
+    // of the most relevant books, most relevant first
+    async fn db_search_books(
+        &self, text: &str, limit: u32, causal: &CausalContext,
+    ) -> Result<Vec<BookSearchResult>, AppError>;
+
+    // Query the book with the key, where only the selected fields of the book need be read
+    async fn db_find_book(
+        &self, key: &BookKey, fields: &FieldSelection, causal: &CausalContext,
+    ) -> Result<Option<Book>, AppError>;
+
+    // Create any indexes the operations depend on which don't yet exist
+    async fn db_ensure_indexes(&self) -> Result<(), AppError>;
+
+    // Insert new book record, assigning the book its id
+    async fn db_insert_book(&self, book: &mut Book, causal: &CausalContext)
+        -> Result<(), AppError>;
+
+    // Update existing book record with the key adding new quantity, returning how many records
+    // were affected
+    async fn db_update_book(
+        &self, key: &BookKey, book: &Book, causal: &CausalContext,
+    ) -> Result<WriteCounts, AppError>;
+
+    // Replace all the fields of the existing book record with the key which clients can change,
+    // returning how many records were affected
+    async fn db_replace_book(
+        &self, key: &BookKey, book: &Book, causal: &CausalContext,
+    ) -> Result<WriteCounts, AppError>;
+
+    // Change just the fields of the existing book record with the key which are provided,
+    // returning how many records were affected
+    async fn db_patch_book(
+        &self, key: &BookKey, book: &Book, causal: &CausalContext,
+    ) -> Result<WriteCounts, AppError>;
+
+    // Delete book record with the key, returning how many were deleted
+    async fn db_delete_book(
+        &self, key: &BookKey, causal: &CausalContext,
+    ) -> Result<WriteCounts, AppError>;
 }
 
 // Book manager
 #[derive(Debug, Clone)]
 pub struct BooksMgr {
+    client: Client,
     coll: Collection<Book>,
+    settings: OperationSettings,
 }
 
-// Manages interaction with books database collection
-//
 impl BooksMgr {
-    // Create new instance of books manager using provided MongoDB URL
+    // Create new instance of books manager for the named collection, using provided MongoDB client
+    // (which may be shared with other apps), where the collection applies the write concern of the
+    // settings to each write and each read applies the read concern of its kind of operation
     //
-    pub async fn new(db_url: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
-        let client = Client::with_uri_str(db_url).await?;
-        let coll = client.database(DB_NAME).collection(COLL_NAME);
-        Ok(Self { coll })
+    pub fn new(
+        client: &Client, db_name: &str, coll_name: &str, settings: &OperationSettings,
+    ) -> Self {
+        let coll_options =
+            CollectionOptions::builder().write_concern(settings.write_concern.clone()).build();
+        let coll = client.database(db_name).collection_with_options(coll_name, coll_options);
+        Self { client: client.clone(), coll, settings: settings.clone() }
     }
 
-    // Query books collection returning list of all books & quantities
+    // Start the session for an operation which is part of a request with the causal context
     //
-    pub async fn db_find_books(&self, book: &Book) -> Result<Vec<Book>, Box<dyn Error>> {
+    async fn start_session(&self, causal: &CausalContext) -> Result<ClientSession, AppError> {
+        causal.start_session(&self.client, self.settings.causal_consistency).await
+    }
+
+    // Start the session for a read of a single book which is part of a request with the causal
+    // context, which isn't causally consistent if the read is linearizable
+    //
+    async fn start_book_read_session(
+        &self, causal: &CausalContext,
+    ) -> Result<ClientSession, AppError> {
+        causal.start_session(&self.client, self.settings.causal_book_reads()).await
+    }
+
+    // Set the book's fields which clients can change on the existing book record with the key,
+    // removing any of those fields not provided if replacing the book
+    //
+    async fn change_book(
+        &self, key: &BookKey, book: &Book, replace: bool, causal: &CausalContext,
+    ) -> Result<WriteCounts, AppError> {
+        let (mut set_doc, unset_fields) = book_changes(book, replace)?;
+        set_doc.insert("last_modified", DateTime::now());
+        let mut update_doc = doc! {"$set": set_doc, "$inc": {"version": 1}};
+
+        if !unset_fields.is_empty() {
+            let unset_doc: Document =
+                unset_fields.iter().map(|field| (field.to_string(), Bson::from(""))).collect();
+            update_doc.insert("$unset", unset_doc);
+        }
+
+        let mut session = self.start_session(causal).await?;
+        let result =
+            self.coll.update_one_with_session(key.filter(), update_doc, None, &mut session).await?;
+        causal.observe(&session);
+        Ok(result.into())
+    }
+}
+
+// Manages interaction with books database collection
+//
+#[async_trait]
+impl BooksStore for BooksMgr {
+    // Query books collection returning a page of books & quantities, where the keyset of each
+    // book's sort field values & id (rather than just a skip) locates the start of the page when a
+    // cursor is provided, so deep pages don't require scanning all the preceding books
+    //
+    async fn db_find_books(
+        &self, filter: &BooksFilter, fields: &FieldSelection, sort: &SortOrder, page: &PageRequest,
+        causal: &CausalContext,
+    ) -> Result<BooksPage, AppError> {
         let mut results = vec![];
-        let filter_doc = if book.title.is_some() && book.author.is_some() {
-            doc! {
-                "title": get_or_err(book.title.as_ref(), "title")?,
-                "author": get_or_err(book.author.as_ref(), "author")?,
-            }
-        } else if book.title.is_some() {
-            doc! {"title": get_or_err(book.title.as_ref(), "title")?,}
-        } else if book.author.is_some() {
-            doc! {"author": get_or_err(book.author.as_ref(), "author")?}
+        let mut session = self.start_session(causal).await?;
+        let filter_doc = books_filter(filter);
+        let total = if page.count_total {
+            Some(
+                self.coll
+                    .count_documents_with_session(
+                        filter_doc.clone(),
+                        CountOptions::builder()
+                            .read_concern(self.settings.listing_read_concern.clone())
+                            .build(),
+                        &mut session,
+                    )
+                    .await?,
+            )
         } else {
-            doc! {}
+            None
+        };
+        let page_filter_doc = match &page.after {
+            Some(after) => doc! {"$and": [filter_doc, after_cursor_filter(after, sort)]},
+            None => filter_doc,
         };
         let find_options = FindOptions::builder()
-            .projection(doc! {
-                "title": 1, "author": 1, "year": 1, "quantity": 1, "explicit": 1,
-                "first_created": 1, "last_modified": 1
-            })
-            .sort(doc! {"year": 1})
+            .projection(fields.projection())
+            .sort(sort_doc(sort))
+            .skip(page.skip)
+            .limit(page.limit.map(|limit| i64::from(limit) + 1))
+            .read_concern(self.settings.listing_read_concern.clone())
             .build();
-        let mut cursor = self.coll.find(filter_doc, find_options).await?;
+        let mut cursor =
+            self.coll.find_with_session(page_filter_doc, find_options, &mut session).await?;
 
-        while let Some(doc) = cursor.next().await {
+        while let Some(doc) = cursor.next(&mut session).await {
             results.push(doc?);
         }
 
+        causal.observe(&session);
+        BooksPage::from_books(results, sort, page, total)
+    }
+
+    // Query books collection returning a stream over the database cursor, so only the current
+    // batch of books is held in memory, where the stream owns the cursor's session (so the causal
+    // context isn't advanced by the books read after the response has started)
+    //
+    async fn db_stream_books(
+        &self, filter: &BooksFilter, fields: &FieldSelection, sort: &SortOrder,
+        causal: &CausalContext,
+    ) -> Result<BookStream, AppError> {
+        let mut session = self.start_session(causal).await?;
+        let find_options = FindOptions::builder()
+            .projection(fields.projection())
+            .sort(sort_doc(sort))
+            .read_concern(self.settings.listing_read_concern.clone())
+            .build();
+        let cursor =
+            self.coll.find_with_session(books_filter(filter), find_options, &mut session).await?;
+        causal.observe(&session);
+        let books = stream::unfold((cursor, session), |(mut cursor, mut session)| async move {
+            let book = cursor.next(&mut session).await?;
+            Some((book.map_err(AppError::from), (cursor, session)))
+        });
+        Ok(books.boxed())
+    }
+
+    // Search books collection using its text index, which matches different forms of each word
+    // (eg. 'triffid' matches 'Triffids') and scores each book's relevance
+    //
+    async fn db_search_books(
+        &self, text: &str, limit: u32, causal: &CausalContext,
+    ) -> Result<Vec<BookSearchResult>, AppError> {
+        let mut results = vec![];
+        let mut session = self.start_session(causal).await?;
+        let mut projection = books_projection();
+        projection.insert(TEXT_SCORE_FIELD, doc! {"$meta": "textScore"});
+        let find_options = FindOptions::builder()
+            .projection(projection)
+            .sort(doc! {TEXT_SCORE_FIELD: {"$meta": "textScore"}, "_id": 1})
+            .limit(i64::from(limit))
+            .read_concern(self.settings.listing_read_concern.clone())
+            .build();
+        let mut cursor = self
+            .coll
+            .clone_with_type::<Document>()
+            .find_with_session(doc! {"$text": {"$search": text}}, find_options, &mut session)
+            .await?;
+
+        while let Some(doc) = cursor.next(&mut session).await {
+            let mut doc = doc?;
+            let relevance = match doc.remove(TEXT_SCORE_FIELD) {
+                Some(Bson::Double(score)) => score,
+                _ => 0.0,
+            };
+            results.push(BookSearchResult { book: bson::from_document(doc)?, relevance });
+        }
+
+        causal.observe(&session);
         Ok(results)
     }
 
-    // Insert new book record
+    // Query books collection for the book with the key
+    //
+    async fn db_find_book(
+        &self, key: &BookKey, fields: &FieldSelection, causal: &CausalContext,
+    ) -> Result<Option<Book>, AppError> {
+        let mut session = self.start_book_read_session(causal).await?;
+        let find_options = FindOneOptions::builder()
+            .projection(fields.projection())
+            .read_concern(self.settings.book_read_concern.clone())
+            .build();
+        let book =
+            self.coll.find_one_with_session(key.filter(), find_options, &mut session).await?;
+        causal.observe(&session);
+        Ok(book)
+    }
+
+    // Create the text index on title & author (with title matches weighted as more relevant) used
+    // by searches, which is a no-op if the index already exists
+    //
+    async fn db_ensure_indexes(&self) -> Result<(), AppError> {
+        let index_options = IndexOptions::builder()
+            .name(String::from(TEXT_INDEX_NAME))
+            .weights(doc! {"title": 10, "author": 5})
+            .default_language(String::from("english"))
+            .build();
+        let index_model = IndexModel::builder()
+            .keys(doc! {"title": "text", "author": "text"})
+            .options(index_options)
+            .build();
+        self.coll.create_index(index_model, None).await?;
+        Ok(())
+    }
+
+    // Insert new book record, with a newly generated id
     //
-    pub async fn db_insert_book(&self, book: &mut Book) -> Result<(), Box<dyn Error>> {
-        err_if_none(&book.title, "title")?;
-        err_if_none(&book.author, "author")?;
-        err_if_none(&book.year, "year")?;
-        err_if_none(&book.quantity, "quantity")?;
+    async fn db_insert_book(
+        &self, book: &mut Book, causal: &CausalContext,
+    ) -> Result<(), AppError> {
+        validate_new_book(book)?;
+        let mut session = self.start_session(causal).await?;
         let now = Some(DateTime::now());
+        book.id = Some(ObjectId::new());
         book.first_created = now;
         book.last_modified = now;
-        self.coll.insert_one(book, None).await?;
+        book.version = Some(1);
+        self.coll.insert_one_with_session(&*book, None, &mut session).await?;
+        causal.observe(&session);
         Ok(())
     }
 
     // Update existing book record adding new quantity
     //
-    pub async fn db_update_book(&self, book: &Book) -> Result<(), Box<dyn Error>> {
-        let title = get_or_err(book.title.as_ref(), "title")?;
-        let author = get_or_err(book.author.as_ref(), "author")?;
+    async fn db_update_book(
+        &self, key: &BookKey, book: &Book, causal: &CausalContext,
+    ) -> Result<WriteCounts, AppError> {
         let quantity = get_or_err(book.quantity.as_ref(), "quantity")?;
-        self.coll
-            .update_one(
-                doc! {"title": title, "author": author},
-                doc! {"$inc": {"quantity": quantity}, "$set": {"last_modified": DateTime::now()}},
+        let mut session = self.start_session(causal).await?;
+        let result = self
+            .coll
+            .update_one_with_session(
+                key.filter(),
+                doc! {
+                    "$inc": {"quantity": quantity, "version": 1},
+                    "$set": {"last_modified": DateTime::now()}
+                },
                 None,
+                &mut session,
             )
             .await?;
-        Ok(())
+        causal.observe(&session);
+        Ok(result.into())
     }
 
-    // Delete book record from books collection which matches book title
+    // Replace the fields of existing book record which clients can change
     //
-    pub async fn db_delete_book(&self, book: &Book) -> Result<(), Box<dyn Error>> {
-        let title = get_or_err(book.title.as_ref(), "title")?;
-        let author = get_or_err(book.author.as_ref(), "author")?;
-        self.coll.delete_one(doc! {"title": title, "author": author}, None).await?;
-        Ok(())
+    async fn db_replace_book(
+        &self, key: &BookKey, book: &Book, causal: &CausalContext,
+    ) -> Result<WriteCounts, AppError> {
+        validate_new_book(book)?;
+        self.change_book(key, book, true, causal).await
+    }
+
+    // Change the provided fields of existing book record
+    //
+    async fn db_patch_book(
+        &self, key: &BookKey, book: &Book, causal: &CausalContext,
+    ) -> Result<WriteCounts, AppError> {
+        self.change_book(key, book, false, causal).await
+    }
+
+    // Delete book record from books collection which matches the key
+    //
+    async fn db_delete_book(
+        &self, key: &BookKey, causal: &CausalContext,
+    ) -> Result<WriteCounts, AppError> {
+        let mut session = self.start_session(causal).await?;
+        let result = self.coll.delete_one_with_session(key.filter(), None, &mut session).await?;
+        causal.observe(&session);
+        Ok(result.into())
     }
 }
 
+// Build a query filter matching books which satisfy the filter's criteria, only ever inserting the
+// criteria's values as typed BSON values (or escaped regular expressions), so query string values
+// can't inject query operators, and where exact or case-sensitive prefix matches on title/author
+// and ranges on year can be bounded by the indexes created by 'data/book-data-prep-for-app1.js'
+//
+fn books_filter(filter: &BooksFilter) -> Document {
+    let mut filter_doc = doc! {};
+
+    if let Some(title) = &filter.title {
+        filter_doc.insert("title", text_match_filter(title));
+    }
+
+    if let Some(author) = &filter.author {
+        filter_doc.insert("author", text_match_filter(author));
+    }
+
+    if let Some(year_range) = range_filter(filter.year_gte, filter.year_lt) {
+        filter_doc.insert("year", year_range);
+    }
+
+    if let Some(quantity_range) = range_filter(filter.quantity_gte, filter.quantity_lt) {
+        filter_doc.insert("quantity", quantity_range);
+    }
+
+    if let Some(explicit) = filter.explicit {
+        filter_doc.insert("explicit", explicit);
+    }
+
+    if let Some(created_range) =
+        range_filter(filter.first_created_since, filter.first_created_before)
+    {
+        filter_doc.insert("first_created", created_range);
+    }
+
+    if let Some(modified_range) =
+        range_filter(filter.last_modified_since, filter.last_modified_before)
+    {
+        filter_doc.insert("last_modified", modified_range);
+    }
+
+    filter_doc
+}
+
+// Build the query filter value for a text field match, using an equality match where possible
+//
+fn text_match_filter(text_match: &TextMatch) -> Bson {
+    if text_match.prefix || text_match.ignore_case {
+        let options = if text_match.ignore_case { "i" } else { "" };
+        doc! {"$regex": text_match.to_regex_pattern(), "$options": options}.into()
+    } else {
+        text_match.value.clone().into()
+    }
+}
+
+// Build the query filter value for a numeric or timestamp field range, if either bound is provided
+//
+fn range_filter<T: Into<Bson>>(gte: Option<T>, lt: Option<T>) -> Option<Document> {
+    let mut range_doc = doc! {};
+
+    if let Some(gte) = gte {
+        range_doc.insert("$gte", gte);
+    }
+
+    if let Some(lt) = lt {
+        range_doc.insert("$lt", lt);
+    }
+
+    (!range_doc.is_empty()).then_some(range_doc)
+}
+
+// Build projection of the book fields owned by app1
+//
+fn books_projection() -> Document {
+    FieldSelection::all(OWNED_FIELDS).projection()
+}
+
+// Build sort specification for the sort order, with the id as the final tiebreaker
+//
+fn sort_doc(sort: &SortOrder) -> Document {
+    let mut sort_doc = doc! {};
+
+    for key in &sort.keys {
+        sort_doc.insert(key.field.name(), if key.descending { -1 } else { 1 });
+    }
+
+    sort_doc.insert("_id", 1);
+    sort_doc
+}
+
+// Build a query filter matching books which come after the cursor's position in the sort order, ie.
+// books which have the same values as the cursor for the first N sort fields and come after it on
+// the next sort field (or on id, if the same for all sort fields)
+//
+fn after_cursor_filter(after: &PageCursor, sort: &SortOrder) -> Document {
+    let mut branches = vec![];
+    let mut same_values_doc = doc! {};
+
+    for (key, value) in sort.keys.iter().zip(&after.values) {
+        let field = key.field.name();
+
+        if let Some(after_value_doc) = after_value_filter(field, value, key.descending) {
+            let mut branch_doc = same_values_doc.clone();
+            branch_doc.extend(after_value_doc);
+            branches.push(branch_doc);
+        }
+
+        same_values_doc.insert(field, value.clone());
+    }
+
+    same_values_doc.insert("_id", doc! {"$gt": after.id});
+    branches.push(same_values_doc);
+    doc! {"$or": branches}
+}
+
+// Build a query filter matching books whose field value comes after the given value in the sort
+// direction, where a missing value (null) sorts before all other values as it does in MongoDB, so
+// nothing comes after null in descending order
+//
+fn after_value_filter(field: &str, value: &Bson, descending: bool) -> Option<Document> {
+    match (value, descending) {
+        (Bson::Null, false) => Some(doc! {field: {"$ne": null}}),
+        (Bson::Null, true) => None,
+        (value, false) => Some(doc! {field: {"$gt": value}}),
+        (value, true) => Some(doc! {"$or": [{field: {"$lt": value}}, {field: null}]}),
+    }
+}
+
+// Build cursor positioned after a book, returning an error if the book was read without its id
+//
+fn page_cursor_after(book: &Book, sort: &SortOrder) -> Result<PageCursor, AppError> {
+    match book.id {
+        Some(id) => Ok(PageCursor { sort: sort.to_string(), values: sort_values(book, sort), id }),
+        None => Err(AppError::Internal(String::from("Book record read without its `_id` field"))),
+    }
+}
+
+// Get a book's values for each of the sort order's fields, using null for any missing value
+//
+pub fn sort_values(book: &Book, sort: &SortOrder) -> Vec<Bson> {
+    sort.keys
+        .iter()
+        .map(|key| {
+            let value = match key.field {
+                SortField::Year => book.year.map(Bson::from),
+                SortField::Title => book.title.clone().map(Bson::from),
+                SortField::Author => book.author.clone().map(Bson::from),
+                SortField::Quantity => book.quantity.map(Bson::from),
+                SortField::LastModified => book.last_modified.map(Bson::from),
+            };
+            value.unwrap_or(Bson::Null)
+        })
+        .collect()
+}
+
+// Split the fields of a book which clients can change into the values to set & the names of the
+// fields to remove, where only a replacement removes the fields which aren't provided
+//
+pub fn book_changes(book: &Book, replace: bool) -> Result<(Document, Vec<&'static str>), AppError> {
+    let changes =
+        Book { id: None, first_created: None, last_modified: None, version: None, ..book.clone() };
+    let set_doc = bson::to_document(&changes)?;
+    let unset_fields = if replace {
+        EDITABLE_FIELDS.iter().copied().filter(|field| !set_doc.contains_key(field)).collect()
+    } else {
+        vec![]
+    };
+    Ok((set_doc, unset_fields))
+}
+
+// Validate a book to be inserted has all the fields required by app1
+//
+pub fn validate_new_book(book: &Book) -> Result<(), AppError> {
+    err_if_none(&book.title, "title")?;
+    err_if_none(&book.author, "author")?;
+    err_if_none(&book.year, "year")?;
+    err_if_none(&book.quantity, "quantity")?;
+    Ok(())
+}
+
 // Validate specific variable field has a value, returning an error if no value
 //
-fn err_if_none<T>(field: &Option<T>, fieldname: &str) -> Result<(), Box<dyn Error>> {
+fn err_if_none<T>(field: &Option<T>, fieldname: &str) -> Result<(), AppError> {
     match field {
         Some(_) => Ok(()),
-        None => Err(format!("Field `{}` is empty, but is required", fieldname).into()),
+        None => Err(AppError::MissingField(fieldname.to_string())),
     }
 }
 
 // Validate specific variable field has a value, returning it, otherwise returning an error
 //
-fn get_or_err<'a, T>(field: Option<&'a T>, fieldname: &str) -> Result<&'a T, Box<dyn Error>> {
-    field.ok_or_else(|| format!("Field `{}` is empty, but is required", fieldname).into())
+pub fn get_or_err<'a, T>(field: Option<&'a T>, fieldname: &str) -> Result<&'a T, AppError> {
+    field.ok_or_else(|| AppError::MissingField(fieldname.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_matches_filtered_literally() {
+        let filter = BooksFilter {
+            title: Some(TextMatch { value: "C++ (2nd".into(), prefix: true, ignore_case: false }),
+            author: Some(TextMatch {
+                value: "A.N. Other".into(),
+                prefix: false,
+                ignore_case: true,
+            }),
+            year_gte: Some(2001),
+            explicit: Some(false),
+            ..BooksFilter::default()
+        };
+        assert_eq!(
+            books_filter(&filter),
+            doc! {
+                "title": {"$regex": r"^C\+\+ \(2nd", "$options": ""},
+                "author": {"$regex": r"^A\.N\. Other$", "$options": "i"},
+                "year": {"$gte": 2001},
+                "explicit": false,
+            }
+        );
+        let exact = TextMatch { value: "Dune".into(), prefix: false, ignore_case: false };
+        let filter = BooksFilter { title: Some(exact), ..BooksFilter::default() };
+        assert_eq!(books_filter(&filter), doc! {"title": "Dune"});
+    }
+
+    #[test]
+    fn sort_ties_broken_by_id() {
+        let sort = SortOrder::parse("-year,title").unwrap();
+        assert_eq!(sort_doc(&sort), doc! {"year": -1, "title": 1, "_id": 1});
+        assert_eq!(sort_doc(&SortOrder::default()), doc! {"year": 1, "_id": 1});
+    }
+
+    #[test]
+    fn after_value_compared_in_sort_direction() {
+        assert_eq!(
+            after_value_filter("year", &Bson::Int32(2001), false),
+            Some(doc! {"year": {"$gt": 2001}})
+        );
+        assert_eq!(
+            after_value_filter("year", &Bson::Int32(2001), true),
+            Some(doc! {"$or": [{"year": {"$lt": 2001}}, {"year": null}]})
+        );
+    }
+
+    #[test]
+    fn after_null_value_only_ascending() {
+        assert_eq!(
+            after_value_filter("year", &Bson::Null, false),
+            Some(doc! {"year": {"$ne": null}})
+        );
+        assert_eq!(after_value_filter("year", &Bson::Null, true), None);
+    }
+
+    #[test]
+    fn after_cursor_ties_broken_by_id() {
+        let sort = SortOrder::parse("author,-year").unwrap();
+        let id = ObjectId::new();
+        let after = PageCursor {
+            sort: sort.to_string(),
+            values: vec![Bson::from("Writer"), Bson::Int32(2001)],
+            id,
+        };
+        assert_eq!(
+            after_cursor_filter(&after, &sort),
+            doc! {"$or": [
+                {"author": {"$gt": "Writer"}},
+                {"author": "Writer", "$or": [{"year": {"$lt": 2001}}, {"year": null}]},
+                {"author": "Writer", "year": 2001, "_id": {"$gt": id}},
+            ]}
+        );
+    }
+
+    #[test]
+    fn after_cursor_with_null_values() {
+        let sort = SortOrder::parse("-year,title").unwrap();
+        let id = ObjectId::new();
+        let after = PageCursor { sort: sort.to_string(), values: vec![Bson::Null, Bson::Null], id };
+        assert_eq!(
+            after_cursor_filter(&after, &sort),
+            doc! {"$or": [
+                {"year": null, "title": {"$ne": null}},
+                {"year": null, "title": null, "_id": {"$gt": id}},
+            ]}
+        );
+    }
 }
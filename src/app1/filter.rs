@@ -0,0 +1,179 @@
+use bson::DateTime;
+
+use crate::error::AppError;
+
+// Value which a book's text field (eg. title) must match, either exactly or as a prefix, and
+// optionally ignoring case
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextMatch {
+    pub value: String,
+    pub prefix: bool,
+    pub ignore_case: bool,
+}
+
+impl TextMatch {
+    // Check whether a text field's value satisfies the match
+    //
+    pub fn matches(&self, text: &str) -> bool {
+        let (text, value) = if self.ignore_case {
+            (text.to_lowercase(), self.value.to_lowercase())
+        } else {
+            (text.to_string(), self.value.clone())
+        };
+
+        if self.prefix {
+            text.starts_with(&value)
+        } else {
+            text == value
+        }
+    }
+
+    // Build a regular expression pattern equivalent to the match, with all special characters in
+    // the value escaped so the value can only ever be matched literally
+    //
+    pub fn to_regex_pattern(&self) -> String {
+        let mut pattern = String::from("^");
+
+        for ch in self.value.chars() {
+            if "\\^$.|?*+()[]{}".contains(ch) {
+                pattern.push('\\');
+            }
+
+            pattern.push(ch);
+        }
+
+        if !self.prefix {
+            pattern.push('$');
+        }
+
+        pattern
+    }
+}
+
+// Criteria for listing books, where a book must satisfy every criterion provided
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BooksFilter {
+    pub title: Option<TextMatch>,
+    pub author: Option<TextMatch>,
+    pub year_gte: Option<i32>,
+    pub year_lt: Option<i32>,
+    pub quantity_gte: Option<i32>,
+    pub quantity_lt: Option<i32>,
+    pub explicit: Option<bool>,
+    pub first_created_since: Option<DateTime>,
+    pub first_created_before: Option<DateTime>,
+    pub last_modified_since: Option<DateTime>,
+    pub last_modified_before: Option<DateTime>,
+}
+
+impl BooksFilter {
+    // Build the match for a text field from its exact & prefix query string values, returning an
+    // error if both are provided
+    //
+    pub fn text_match(
+        field: &str, exact: Option<&String>, prefix: Option<&String>, ignore_case: bool,
+    ) -> Result<Option<TextMatch>, AppError> {
+        match (exact, prefix) {
+            (Some(_), Some(_)) => Err(AppError::InvalidField {
+                field: format!("{}_prefix", field),
+                reason: format!("cannot be combined with `{}`", field),
+            }),
+            (Some(value), None) => {
+                Ok(Some(TextMatch { value: value.clone(), prefix: false, ignore_case }))
+            }
+            (None, Some(value)) => {
+                Ok(Some(TextMatch { value: value.clone(), prefix: true, ignore_case }))
+            }
+            (None, None) => Ok(None),
+        }
+    }
+
+    // Parse an RFC 3339 timestamp query string value, returning an error naming the field if the
+    // value is not a valid timestamp
+    //
+    pub fn timestamp(field: &str, value: Option<&String>) -> Result<Option<DateTime>, AppError> {
+        value
+            .map(|value| {
+                DateTime::parse_rfc3339_str(value).map_err(|_| AppError::InvalidField {
+                    field: field.to_string(),
+                    reason: String::from("must be an RFC 3339 timestamp, eg. 2021-12-31T23:59:59Z"),
+                })
+            })
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Match of the value, as a prefix or exactly, and optionally ignoring case
+    //
+    fn text_match(value: &str, prefix: bool, ignore_case: bool) -> TextMatch {
+        TextMatch { value: value.to_string(), prefix, ignore_case }
+    }
+
+    #[test]
+    fn text_matched_exactly_or_by_prefix() {
+        assert!(text_match("Dune", false, false).matches("Dune"));
+        assert!(!text_match("Dune", false, false).matches("Dune Messiah"));
+        assert!(text_match("Dune", true, false).matches("Dune Messiah"));
+        assert!(!text_match("dune", true, false).matches("Dune Messiah"));
+        assert!(text_match("dune", true, true).matches("Dune Messiah"));
+        assert!(text_match("DUNE", false, true).matches("Dune"));
+    }
+
+    #[test]
+    fn regex_pattern_matches_value_literally() {
+        assert_eq!(text_match("Dune", false, false).to_regex_pattern(), "^Dune$");
+        assert_eq!(text_match("Dune", true, false).to_regex_pattern(), "^Dune");
+        assert_eq!(
+            text_match("C++ (2nd ed.)", true, true).to_regex_pattern(),
+            r"^C\+\+ \(2nd ed\.\)"
+        );
+        assert_eq!(
+            text_match(r"^.*$|[a]{1}?\", true, false).to_regex_pattern(),
+            r"^\^\.\*\$\|\[a\]\{1\}\?\\"
+        );
+    }
+
+    #[test]
+    fn exact_or_prefix_match_built() {
+        let value = String::from("Dune");
+        assert_eq!(BooksFilter::text_match("title", None, None, false).unwrap(), None);
+        let exact = BooksFilter::text_match("title", Some(&value), None, true).unwrap();
+        assert_eq!(exact, Some(text_match("Dune", false, true)));
+        let prefix = BooksFilter::text_match("title", None, Some(&value), false).unwrap();
+        assert_eq!(prefix, Some(text_match("Dune", true, false)));
+    }
+
+    #[test]
+    fn exact_and_prefix_match_rejected_together() {
+        let value = String::from("Dune");
+
+        match BooksFilter::text_match("author", Some(&value), Some(&value), false) {
+            Err(AppError::InvalidField { field, reason }) => {
+                assert_eq!(field, "author_prefix");
+                assert!(reason.contains("`author`"));
+            }
+            other => panic!("Expected invalid field error, not {:?}", other),
+        }
+    }
+
+    #[test]
+    fn timestamps_parsed_as_rfc_3339() {
+        let value = String::from("2021-12-31T23:59:59Z");
+        let timestamp = BooksFilter::timestamp("last_modified_since", Some(&value)).unwrap();
+        assert_eq!(timestamp.unwrap().timestamp_millis(), 1_640_995_199_000);
+        assert_eq!(BooksFilter::timestamp("last_modified_since", None).unwrap(), None);
+
+        for value in ["2021-12-31", "yesterday", "1640995199"] {
+            match BooksFilter::timestamp("first_created_before", Some(&value.to_string())) {
+                Err(AppError::InvalidField { field, .. }) => {
+                    assert_eq!(field, "first_created_before")
+                }
+                other => panic!("Expected invalid field error, not {:?}", other),
+            }
+        }
+    }
+}
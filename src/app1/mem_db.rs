@@ -0,0 +1,362 @@
+use async_trait::async_trait;
+use bson::{oid::ObjectId, Bson, DateTime};
+use futures::{stream, StreamExt};
+use std::cmp::Ordering;
+
+use super::db::{
+    book_changes, get_or_err, sort_values, validate_new_book, Book, BookSearchResult, BookStream,
+    BooksPage, BooksStore,
+};
+use super::filter::{BooksFilter, TextMatch};
+use super::paging::PageRequest;
+use super::sorting::SortOrder;
+use crate::causal::CausalContext;
+use crate::error::AppError;
+use crate::fields::FieldSelection;
+use crate::mem_store::{compare_values, inc_field, MemCollection};
+use crate::store::{BookKey, WriteCounts};
+
+const TITLE_WEIGHT: f64 = 10.0;
+const AUTHOR_WEIGHT: f64 = 5.0;
+
+// In-memory book manager, for running app1 without a MongoDB database
+#[derive(Debug, Clone, Default)]
+pub struct MemBooksMgr {
+    coll: MemCollection,
+}
+
+impl MemBooksMgr {
+    // Create new instance of in-memory books manager over the provided in-memory collection
+    //
+    pub fn new(coll: MemCollection) -> Self {
+        Self { coll }
+    }
+
+    // Set the book's fields which clients can change on the existing book record with the key,
+    // removing any of those fields not provided if replacing the book
+    //
+    fn change_book(
+        &self, key: &BookKey, book: &Book, replace: bool,
+    ) -> Result<WriteCounts, AppError> {
+        let (set_doc, unset_fields) = book_changes(book, replace)?;
+        self.coll.update_one(
+            |doc| key.matches(doc),
+            |doc| {
+                doc.extend(set_doc);
+
+                for field in unset_fields {
+                    doc.remove(field);
+                }
+
+                doc.insert("last_modified", DateTime::now());
+                inc_field(doc, "version", 1)?;
+                Ok(true)
+            },
+        )
+    }
+}
+
+// Manages interaction with in-memory books collection, emulating the MongoDB books manager, where
+// every operation trivially observes all earlier operations, so the causal context is unused
+//
+#[async_trait]
+impl BooksStore for MemBooksMgr {
+    // Query books returning a page of books, in the sort order, and starting after the cursor's
+    // position in that order if provided, where the whole of each book is returned regardless of
+    // the field selection as the records are already in memory
+    //
+    async fn db_find_books(
+        &self, filter: &BooksFilter, _fields: &FieldSelection, sort: &SortOrder,
+        page: &PageRequest, _causal: &CausalContext,
+    ) -> Result<BooksPage, AppError> {
+        let docs = self.coll.find(|_| true);
+        let mut results = vec![];
+
+        for doc in docs {
+            let book: Book = bson::from_document(doc)?;
+
+            if book_matches(&book, filter) {
+                results.push(book);
+            }
+        }
+
+        let total = page.count_total.then_some(results.len() as u64);
+        let mut results: Vec<(Vec<Bson>, Bson, Book)> = results
+            .into_iter()
+            .map(|book| (sort_values(&book, sort), Bson::from(book.id), book))
+            .collect();
+        results.sort_by(|(values1, id1, _), (values2, id2, _)| {
+            compare_in_sort_order((values1, id1), (values2, id2), sort)
+        });
+
+        if let Some(after) = &page.after {
+            let after_id = Bson::from(after.id);
+            results.retain(|(values, id, _)| {
+                compare_in_sort_order((values, id), (&after.values, &after_id), sort)
+                    == Ordering::Greater
+            });
+        }
+
+        let skip = page.skip.unwrap_or_default() as usize;
+        let limit = page.limit.map_or(usize::MAX, |limit| limit as usize + 1);
+        let results = results.into_iter().skip(skip).take(limit).map(|(_, _, book)| book).collect();
+        BooksPage::from_books(results, sort, page, total)
+    }
+
+    // Query books returning a stream over a snapshot of all matching books
+    //
+    async fn db_stream_books(
+        &self, filter: &BooksFilter, fields: &FieldSelection, sort: &SortOrder,
+        causal: &CausalContext,
+    ) -> Result<BookStream, AppError> {
+        let page = PageRequest::default();
+        let books = self.db_find_books(filter, fields, sort, &page, causal).await?.books;
+        Ok(stream::iter(books.into_iter().map(Ok)).boxed())
+    }
+
+    // Search books naively, scoring each book by how many of its title & author words start with
+    // one of the search words (ignoring case), where title matches are weighted as more relevant
+    //
+    async fn db_search_books(
+        &self, text: &str, limit: u32, _causal: &CausalContext,
+    ) -> Result<Vec<BookSearchResult>, AppError> {
+        let terms = words(text);
+        let mut results = vec![];
+
+        for doc in self.coll.find(|_| true) {
+            let book: Book = bson::from_document(doc)?;
+            let relevance = TITLE_WEIGHT * term_matches(book.title.as_deref(), &terms)
+                + AUTHOR_WEIGHT * term_matches(book.author.as_deref(), &terms);
+
+            if relevance > 0.0 {
+                results.push(BookSearchResult { book, relevance });
+            }
+        }
+
+        results.sort_by(|result1, result2| {
+            result2
+                .relevance
+                .total_cmp(&result1.relevance)
+                .then_with(|| compare_values(&result1.book.id.into(), &result2.book.id.into()))
+        });
+        results.truncate(limit as usize);
+        Ok(results)
+    }
+
+    // Query the book with the key, where the whole of the book is returned regardless of the field
+    // selection as the records are already in memory
+    //
+    async fn db_find_book(
+        &self, key: &BookKey, _fields: &FieldSelection, _causal: &CausalContext,
+    ) -> Result<Option<Book>, AppError> {
+        let doc = self.coll.find_one(|doc| key.matches(doc));
+        Ok(doc.map(bson::from_document).transpose()?)
+    }
+
+    // No indexes are needed by the in-memory store
+    //
+    async fn db_ensure_indexes(&self) -> Result<(), AppError> {
+        Ok(())
+    }
+
+    // Insert new book record, with a newly generated id
+    //
+    async fn db_insert_book(
+        &self, book: &mut Book, _causal: &CausalContext,
+    ) -> Result<(), AppError> {
+        validate_new_book(book)?;
+        let now = Some(DateTime::now());
+        book.id = Some(ObjectId::new());
+        book.first_created = now;
+        book.last_modified = now;
+        book.version = Some(1);
+        self.coll.insert_one(bson::to_document(book)?)?;
+        Ok(())
+    }
+
+    // Update existing book record adding new quantity
+    //
+    async fn db_update_book(
+        &self, key: &BookKey, book: &Book, _causal: &CausalContext,
+    ) -> Result<WriteCounts, AppError> {
+        let quantity = *get_or_err(book.quantity.as_ref(), "quantity")?;
+        self.coll.update_one(
+            |doc| key.matches(doc),
+            |doc| {
+                inc_field(doc, "quantity", quantity)?;
+                doc.insert("last_modified", DateTime::now());
+                inc_field(doc, "version", 1)?;
+                Ok(true)
+            },
+        )
+    }
+
+    // Replace the fields of existing book record which clients can change
+    //
+    async fn db_replace_book(
+        &self, key: &BookKey, book: &Book, _causal: &CausalContext,
+    ) -> Result<WriteCounts, AppError> {
+        validate_new_book(book)?;
+        self.change_book(key, book, true)
+    }
+
+    // Change the provided fields of existing book record
+    //
+    async fn db_patch_book(
+        &self, key: &BookKey, book: &Book, _causal: &CausalContext,
+    ) -> Result<WriteCounts, AppError> {
+        self.change_book(key, book, false)
+    }
+
+    // Delete book record which matches the key
+    //
+    async fn db_delete_book(
+        &self, key: &BookKey, _causal: &CausalContext,
+    ) -> Result<WriteCounts, AppError> {
+        Ok(self.coll.delete_one(|doc| key.matches(doc)))
+    }
+}
+
+// Split text into lowercase words, at any character which isn't alphanumeric
+//
+fn words(text: &str) -> Vec<String> {
+    text.split(|ch: char| !ch.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+// Count how many words of a text field start with any of the search terms
+//
+fn term_matches(text: Option<&str>, terms: &[String]) -> f64 {
+    let matches = words(text.unwrap_or_default())
+        .iter()
+        .filter(|word| terms.iter().any(|term| word.starts_with(term.as_str())))
+        .count();
+    matches as f64
+}
+
+// Compare the sort field values & ids of two books in the sort order, emulating MongoDB's ordering
+//
+fn compare_in_sort_order(
+    (values1, id1): (&[Bson], &Bson), (values2, id2): (&[Bson], &Bson), sort: &SortOrder,
+) -> Ordering {
+    sort.keys
+        .iter()
+        .zip(values1.iter().zip(values2))
+        .map(|(key, (val1, val2))| {
+            let ordering = compare_values(val1, val2);
+            if key.descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        })
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or_else(|| compare_values(id1, id2))
+}
+
+// Check whether a book satisfies every criterion of the filter, emulating the MongoDB query filter,
+// where a book missing a field never satisfies a criterion on that field
+//
+fn book_matches(book: &Book, filter: &BooksFilter) -> bool {
+    text_matches(book.title.as_deref(), filter.title.as_ref())
+        && text_matches(book.author.as_deref(), filter.author.as_ref())
+        && in_range(book.year, filter.year_gte, filter.year_lt)
+        && in_range(book.quantity, filter.quantity_gte, filter.quantity_lt)
+        && filter.explicit.is_none_or(|explicit| book.explicit == Some(explicit))
+        && in_range(book.first_created, filter.first_created_since, filter.first_created_before)
+        && in_range(book.last_modified, filter.last_modified_since, filter.last_modified_before)
+}
+
+// Check whether a text field satisfies the match, if any
+//
+fn text_matches(text: Option<&str>, text_match: Option<&TextMatch>) -> bool {
+    match text_match {
+        Some(text_match) => text.is_some_and(|text| text_match.matches(text)),
+        None => true,
+    }
+}
+
+// Check whether a numeric or timestamp field falls within the range, if any bound is provided
+//
+fn in_range<T: PartialOrd + Copy>(val: Option<T>, gte: Option<T>, lt: Option<T>) -> bool {
+    if gte.is_none() && lt.is_none() {
+        return true;
+    }
+
+    val.is_some_and(|val| gte.is_none_or(|gte| val >= gte) && lt.is_none_or(|lt| val < lt))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app1::db::OWNED_FIELDS;
+    use bson::doc;
+
+    const PAGE_LIMIT: u32 = 2;
+
+    // In-memory books manager over books with many equal & missing values for the sort fields
+    //
+    fn books_mgr() -> MemBooksMgr {
+        let coll = MemCollection::default();
+        let books = [
+            doc! {"title": "Book A", "author": "Writer X", "year": 2001},
+            doc! {"title": "Book B", "author": "Writer Y", "year": 2001},
+            doc! {"title": "Book C", "author": "Writer X", "year": 2002},
+            doc! {"title": "Book D", "author": "Writer X"},
+            doc! {"title": "Book E", "author": "Writer Y", "year": 2001},
+            doc! {"title": "Book F", "author": "Writer Y"},
+            doc! {"title": "Book G", "author": "Writer X", "year": 2002},
+        ];
+
+        for book in books {
+            coll.insert_one(book).unwrap();
+        }
+
+        MemBooksMgr::new(coll)
+    }
+
+    // Titles of the books listed in the sort order, by following each page's cursor to the next
+    // page, or by listing all of them at once
+    //
+    async fn listed_titles(mgr: &MemBooksMgr, sort: &SortOrder, paged: bool) -> Vec<String> {
+        let (filter, fields) = (BooksFilter::default(), FieldSelection::all(OWNED_FIELDS));
+        let limit = paged.then_some(PAGE_LIMIT);
+        let mut page = PageRequest { limit, ..PageRequest::default() };
+        let mut titles = vec![];
+
+        loop {
+            let causal = CausalContext::default();
+            let found = mgr.db_find_books(&filter, &fields, sort, &page, &causal).await.unwrap();
+            assert!(found.books.len() <= limit.unwrap_or(u32::MAX) as usize);
+            titles.extend(found.books.into_iter().map(|book| book.title.unwrap()));
+
+            match found.next {
+                Some(next) => page.after = Some(next),
+                None => return titles,
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn pages_neither_skip_nor_repeat_books() {
+        let mgr = books_mgr();
+
+        for sort in ["year", "-year", "author,-year", "-author,year,-title", "quantity"] {
+            let sort = SortOrder::parse(sort).unwrap();
+            let all_titles = listed_titles(&mgr, &sort, false).await;
+            assert_eq!(all_titles.len(), 7);
+            assert_eq!(listed_titles(&mgr, &sort, true).await, all_titles, "sort: {}", sort);
+        }
+    }
+
+    #[tokio::test]
+    async fn missing_values_sorted_first() {
+        let mgr = books_mgr();
+        let titles = listed_titles(&mgr, &SortOrder::parse("year,title").unwrap(), true).await;
+        assert_eq!(titles, ["Book D", "Book F", "Book A", "Book B", "Book E", "Book C", "Book G"]);
+        let titles = listed_titles(&mgr, &SortOrder::parse("-year,-title").unwrap(), true).await;
+        assert_eq!(titles, ["Book G", "Book C", "Book E", "Book B", "Book A", "Book F", "Book D"]);
+    }
+}
@@ -1,37 +1,207 @@
+use bson::DateTime;
+use futures::StreamExt;
 use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
 use std::error::Error;
-use std::net::Ipv4Addr;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
 use warp::{http, Filter};
 
+use crate::causal::{capture_causal_context, causal_reply, CausalContext};
+use crate::conditional::{
+    capture_if_match_header, capture_read_conditions, if_match_versions, versioned_reply,
+    ReadConditions, Version,
+};
+use crate::config::App1Config;
+use crate::error::{handle_rejection, AppError};
+use crate::fields::FieldSelection;
+use crate::store::{BookKey, OperationSettings, StoreConnection, StoreLocation};
+
 mod db;
-use db::{Book, BooksMgr};
+use db::{Book, BookSearchResult, BooksMgr, BooksPage, BooksStore, OWNED_FIELDS};
+
+mod mem_db;
+use mem_db::MemBooksMgr;
+
+mod filter;
+use filter::BooksFilter;
+
+mod paging;
+use paging::{validate_limit, PageRequest};
+
+mod sorting;
+use sorting::SortOrder;
+
+mod streaming;
+use streaming::{streamed_json_response, StreamFormat};
 
-const LISTEN_ADDRESS: Ipv4Addr = Ipv4Addr::new(127, 0, 0, 1);
-const LISTEN_PORT: u16 = 8181;
 const RSC_VERSION: &str = "v1";
 const RSC_NAME: &str = "books";
-const PAYLOAD_LIMIT: u64 = 1024 * 16;
+const SEARCH_RSC_NAME: &str = "search";
 
-// Book record to extract from/to JSON payload
+// Book record to extract from/to JSON payload, where the id and the times of creation & last
+// modification (as RFC 3339 timestamps) are only ever set by the DB tier, so are ignored in request
+// payloads
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct BookPayload {
+    pub id: Option<String>,
     pub title: Option<String>,
     pub author: Option<String>,
     pub year: Option<i32>,
     pub quantity: Option<i32>,
     pub explicit: Option<bool>,
+    pub first_created: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+// Query string parameters for listing books, optionally filtered by title/author (exactly or by
+// prefix, and optionally ignoring case), year & quantity ranges, explicitness and ranges of the
+// times of creation & last modification (as RFC 3339 timestamps), sorted by a list of fields (eg.
+// 'author,-year') and narrowed to a list of fields (eg. 'title,quantity'), where providing any of
+// the paging fields returns a single page of books in a response envelope, and providing the
+// stream field instead returns all books in a streamed response body
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct BooksQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title_prefix: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author_prefix: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ignore_case: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub year_gte: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub year_lt: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quantity_gte: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quantity_lt: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub explicit: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_created_since: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_created_before: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_modified_since: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_modified_before: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fields: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub count: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream: Option<String>,
+}
+
+// Query string parameters for reading a single book, optionally narrowed to a list of fields
+#[derive(Debug, Deserialize, Clone)]
+pub struct BookQuery {
+    pub fields: Option<String>,
+}
+
+// Page of books JSON payload, narrowed to the requested fields, with a link to the next page (if
+// any) and the total number of matching books (if requested)
+#[derive(Debug, Serialize, Clone)]
+pub struct BooksPagePayload {
+    pub books: serde_json::Value,
+    pub next: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<u64>,
+}
+
+// Query string parameters for searching books by words in their title or author
+#[derive(Debug, Deserialize, Clone)]
+pub struct SearchQuery {
+    pub q: Option<String>,
+    pub limit: Option<u32>,
+}
+
+// Book search result JSON payload, where a higher relevance indicates a better match
+#[derive(Debug, Serialize, Clone)]
+pub struct BookSearchPayload {
+    pub id: Option<String>,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub year: Option<i32>,
+    pub quantity: Option<i32>,
+    pub explicit: Option<bool>,
+    pub first_created: Option<String>,
+    pub last_modified: Option<String>,
+    pub relevance: f64,
+}
+
+// App1 main function to setup books manager REST API service, over the connection to the store at
+// the location, until the shutdown future completes, when requests in progress are finished first
+//
+pub async fn app1_main(
+    location: &StoreLocation, connection: &StoreConnection, listen: SocketAddr,
+    config: &App1Config, shutdown: impl Future<Output = ()> + Send + 'static,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    println!("App1 running against {}", location);
+    let books_mgr = books_store(connection, &config.operation_settings()?);
+
+    if config.ensure_indexes {
+        books_mgr.db_ensure_indexes().await?;
+    }
+
+    let routes = books_routes(books_mgr, config.payload_limit);
+    let (_, server) = warp::serve(routes)
+        .try_bind_with_graceful_shutdown(listen, shutdown)
+        .map_err(|e| format!("Can't listen on {}: {}", listen, e))?;
+    println!("- HTTP REST API listening on: http://{}/{}/{}", listen, RSC_VERSION, RSC_NAME);
+    println!(
+        "- Eg: http://{}/{}/{}?title=The%20Day%20of%20the%20Triffids&author=John%20Wyndham",
+        listen, RSC_VERSION, RSC_NAME
+    );
+    println!("- Eg: http://{}/{}/{}?limit=5&count=true", listen, RSC_VERSION, RSC_NAME);
+    println!("- Eg: http://{}/{}/{}?fields=title,quantity", listen, RSC_VERSION, RSC_NAME);
+    println!("- Eg: http://{}/{}/{}?stream=ndjson", listen, RSC_VERSION, RSC_NAME);
+    println!("- Eg: http://{}/{}/{}/{}?q=triffid", listen, RSC_VERSION, RSC_NAME, SEARCH_RSC_NAME);
+    println!("- Eg: http://{}/{}/{}/{{id}}", listen, RSC_VERSION, RSC_NAME);
+    server.await;
+    Ok(())
 }
 
-// App1 main function to setup books manager REST API service
+// Filter chains of the books manager REST API, routing each request to its handler, where any
+// rejection becomes an error response
 //
-pub async fn app1_main(url: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
-    println!("App1 running against MongoDB database at '{}'", url);
-    let books_mgr = BooksMgr::new(url).await?;
+fn books_routes(
+    books_mgr: Arc<dyn BooksStore>, payload_limit: u64,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = Infallible> + Clone {
     let books_mgr_ref = warp::any().map(move || books_mgr.clone());
+    // Each request's operations run in the causal context of the client's earlier requests
+    let causal_books_mgr_ref = capture_causal_context().and(books_mgr_ref);
     let api_path_filter_chain =
         warp::path(RSC_VERSION).and(warp::path(RSC_NAME)).and(warp::path::end());
-    let api_path_json_capture_filter_chain =
-        api_path_filter_chain.and(capture_book_body_json()).and(books_mgr_ref.clone());
+    let api_path_json_capture_filter_chain = api_path_filter_chain
+        .and(capture_book_body_json(payload_limit))
+        .and(causal_books_mgr_ref.clone());
+    let api_path_json_conditional_filter_chain = api_path_filter_chain
+        .and(capture_book_body_json(payload_limit))
+        .and(capture_if_match_header())
+        .and(causal_books_mgr_ref.clone());
+    let api_item_path_filter_chain = warp::path(RSC_VERSION)
+        .and(warp::path(RSC_NAME))
+        .and(warp::path::param::<String>())
+        .and(warp::path::end());
+    let api_item_path_json_conditional_filter_chain = api_item_path_filter_chain
+        .and(capture_book_body_json(payload_limit))
+        .and(capture_if_match_header())
+        .and(causal_books_mgr_ref.clone());
     // CREATE: HTTP POST filter chain
     let add_items =
         warp::post().and(api_path_json_capture_filter_chain.clone()).and_then(insert_book_list);
@@ -39,107 +209,421 @@ pub async fn app1_main(url: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
     let get_items = warp::get()
         .and(api_path_filter_chain)
         .and(capture_book_query_string())
-        .and(books_mgr_ref.clone())
+        .and(capture_read_conditions())
+        .and(causal_books_mgr_ref.clone())
         .and_then(get_books_list);
-    // UPDATE: HTTP PUT filter chain
-    let update_item =
-        warp::put().and(api_path_json_capture_filter_chain.clone()).and_then(update_book_list);
-    // DELETE: HTTP DELETE filter chain
-    let delete_item =
-        warp::delete().and(api_path_json_capture_filter_chain.clone()).and_then(delete_book_list);
-    let routes = add_items.or(get_items).or(update_item).or(delete_item);
-    println!(
-        "- HTTP REST API listening on: http://{}:{}/{}/{}",
-        LISTEN_ADDRESS, LISTEN_PORT, RSC_VERSION, RSC_NAME
-    );
-    println!(
-        "- Eg: http://{}:{}/{}/{}?title=The%20Day%20of%20the%20Triffids&author=John%20Wyndham",
-        LISTEN_ADDRESS, LISTEN_PORT, RSC_VERSION, RSC_NAME
-    );
-    warp::serve(routes).run((LISTEN_ADDRESS, LISTEN_PORT)).await;
-    Ok(())
+    let search_items = warp::get()
+        .and(warp::path(RSC_VERSION))
+        .and(warp::path(RSC_NAME))
+        .and(warp::path(SEARCH_RSC_NAME))
+        .and(warp::path::end())
+        .and(warp::query::query())
+        .and(causal_books_mgr_ref.clone())
+        .and_then(search_books_list);
+    let get_item = warp::get()
+        .and(api_item_path_filter_chain)
+        .and(warp::query::query())
+        .and(capture_read_conditions())
+        .and(causal_books_mgr_ref.clone())
+        .and_then(get_book_item);
+    // UPDATE: HTTP PUT & PATCH filter chains
+    let update_items =
+        warp::put().and(api_path_json_conditional_filter_chain.clone()).and_then(update_book_list);
+    let replace_item = warp::put()
+        .and(api_item_path_json_conditional_filter_chain.clone())
+        .and_then(replace_book_item);
+    let patch_item = warp::patch()
+        .and(api_item_path_json_conditional_filter_chain.clone())
+        .and_then(patch_book_item);
+    // DELETE: HTTP DELETE filter chains
+    let delete_items = warp::delete()
+        .and(api_path_json_conditional_filter_chain.clone())
+        .and_then(delete_book_list);
+    let delete_item = warp::delete()
+        .and(api_item_path_filter_chain)
+        .and(capture_if_match_header())
+        .and(causal_books_mgr_ref.clone())
+        .and_then(delete_book_item);
+    add_items
+        .or(get_items)
+        .or(search_items)
+        .or(get_item)
+        .or(update_items)
+        .or(replace_item)
+        .or(patch_item)
+        .or(delete_items)
+        .or(delete_item)
+        .recover(handle_rejection)
+}
+
+// Create the indexes app1 relies on in the books store, if not already present
+//
+pub async fn app1_ensure_indexes(connection: &StoreConnection) -> Result<(), AppError> {
+    books_store(connection, &OperationSettings::default()).db_ensure_indexes().await
+}
+
+// Create books manager over the connection to the store, with the settings of its operations
+//
+fn books_store(connection: &StoreConnection, settings: &OperationSettings) -> Arc<dyn BooksStore> {
+    match connection {
+        StoreConnection::InMemory(coll) => Arc::new(MemBooksMgr::new(coll.clone())),
+        StoreConnection::MongoDb { client, db_name, coll_name } => {
+            Arc::new(BooksMgr::new(client, db_name, coll_name, settings))
+        }
+    }
 }
 
 // Capture book http query string parameters
 //
 fn capture_book_query_string(
-) -> impl Filter<Extract = (BookPayload,), Error = warp::Rejection> + Clone {
+) -> impl Filter<Extract = (BooksQuery,), Error = warp::Rejection> + Clone {
     warp::query::query()
 }
 
-// Capture book http request payload JSON content
+// Capture book http request payload JSON content, rejecting payloads larger than the limit
 //
 fn capture_book_body_json(
+    payload_limit: u64,
 ) -> impl Filter<Extract = (BookPayload,), Error = warp::Rejection> + Clone {
-    warp::body::content_length_limit(PAYLOAD_LIMIT).and(warp::body::json())
+    warp::body::content_length_limit(payload_limit).and(warp::body::json())
 }
 
-// Insert book record in back-end DB
+// Insert book record in back-end DB, responding with the location of the new book's resource
 //
 async fn insert_book_list(
-    book_payload: BookPayload, books_mgr: BooksMgr,
+    book_payload: BookPayload, causal: CausalContext, books_mgr: Arc<dyn BooksStore>,
 ) -> Result<impl warp::Reply, warp::Rejection> {
-    match books_mgr.db_insert_book(&mut book_payload_to_book(&book_payload)).await {
-        Ok(_) => Ok(warp::reply::with_status(
-            "Inserted new book into the book list",
-            http::StatusCode::CREATED,
+    let mut book = book_payload_to_book(&book_payload);
+
+    match books_mgr.db_insert_book(&mut book, &causal).await {
+        Ok(_) => Ok(causal_reply(
+            warp::reply::with_header(
+                warp::reply::with_status(
+                    "Inserted new book into the book list",
+                    http::StatusCode::CREATED,
+                ),
+                http::header::LOCATION,
+                book_location(&book),
+            ),
+            &causal,
         )),
         Err(e) => {
-            eprintln!("Error inserting data: {:#?}", e);
-            Err(warp::reject())
+            eprintln!("Error inserting data: {}", e);
+            Err(warp::reject::custom(e))
         }
     }
 }
 
-// Update book record in back-end DB
+// Update book record in back-end DB, identified by the payload's title & author, if still at a
+// version the request requires
 //
 async fn update_book_list(
-    book_payload: BookPayload, books_mgr: BooksMgr,
+    book_payload: BookPayload, if_match: Option<String>, causal: CausalContext,
+    books_mgr: Arc<dyn BooksStore>,
 ) -> Result<impl warp::Reply, warp::Rejection> {
-    match books_mgr.db_update_book(&book_payload_to_book(&book_payload)).await {
-        Ok(_) => Ok(warp::reply::with_status(
-            "Incremented book amount in the book list",
-            http::StatusCode::CREATED,
+    let key = book_payload_to_key(&book_payload).map_err(warp::reject::custom)?;
+    let key = key.at_versions(if_match_versions(if_match.as_deref()));
+
+    match books_mgr.db_update_book(&key, &book_payload_to_book(&book_payload), &causal).await {
+        Ok(counts) if counts.is_unmatched() => Err(unmatched_book(&key, &causal, &books_mgr).await),
+        Ok(_) => Ok(causal_reply(
+            warp::reply::with_status(
+                "Incremented book amount in the book list",
+                http::StatusCode::OK,
+            ),
+            &causal,
         )),
         Err(e) => {
-            eprintln!("Error updating data: {:#?}", e);
-            Err(warp::reject())
+            eprintln!("Error updating data: {}", e);
+            Err(warp::reject::custom(e))
         }
     }
 }
 
-// Find all book records from back-end DB
+// Find all book records from back-end DB, or just a page of them in an envelope if any paging
+// fields are provided, or stream all of them if the stream field is provided, where only the
+// requested fields of each book are read and returned, and where a listing which isn't streamed is
+// not returned again if the client already has the same version of it
 //
 async fn get_books_list(
-    book_payload: BookPayload, books_mgr: BooksMgr,
+    books_query: BooksQuery, conditions: ReadConditions, causal: CausalContext,
+    books_mgr: Arc<dyn BooksStore>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let paged = books_query.limit.is_some()
+        || books_query.skip.is_some()
+        || books_query.cursor.is_some()
+        || books_query.count.is_some();
+    let filter = books_query_to_filter(&books_query).map_err(warp::reject::custom)?;
+    let sort = books_query.sort.as_deref().map(SortOrder::parse).transpose();
+    let sort = sort.map_err(warp::reject::custom)?.unwrap_or_default();
+    let fields = books_query.fields.as_deref();
+    let fields = fields.map(|fields| FieldSelection::parse(fields, OWNED_FIELDS)).transpose();
+    let fields =
+        fields.map_err(warp::reject::custom)?.unwrap_or_else(|| FieldSelection::all(OWNED_FIELDS));
+    let projected = projected_fields(&fields, &sort);
+
+    if let Some(format) = &books_query.stream {
+        if paged {
+            return Err(warp::reject::custom(AppError::InvalidField {
+                field: String::from("stream"),
+                reason: String::from("cannot be combined with the paging fields"),
+            }));
+        }
+
+        let format = StreamFormat::parse(format).map_err(warp::reject::custom)?;
+        return stream_books_list(&filter, &fields, &projected, &sort, &causal, books_mgr, format)
+            .await;
+    }
+
+    let page = if paged {
+        PageRequest::new(
+            books_query.limit,
+            books_query.skip,
+            books_query.cursor.as_deref(),
+            books_query.count.unwrap_or_default(),
+            &sort,
+        )
+        .map_err(warp::reject::custom)?
+    } else {
+        PageRequest::default()
+    };
+
+    match books_mgr.db_find_books(&filter, &projected, &sort, &page, &causal).await {
+        Ok(result) if paged => {
+            let payload = books_page_to_books_page_payload(&result, &fields, &books_query)
+                .map_err(warp::reject::custom)?;
+            let version = Version::of_listing(&payload, latest_modification(&result.books))
+                .map_err(warp::reject::custom)?;
+            let reply = versioned_reply(warp::reply::json(&payload), Some(&version), &conditions);
+            Ok(causal_reply(reply, &causal))
+        }
+        Ok(result) => {
+            let payload = fields
+                .narrow(&books_to_books_payload(&result.books))
+                .map_err(warp::reject::custom)?;
+            let version = Version::of_listing(&payload, latest_modification(&result.books))
+                .map_err(warp::reject::custom)?;
+            let reply = versioned_reply(warp::reply::json(&payload), Some(&version), &conditions);
+            Ok(causal_reply(reply, &causal))
+        }
+        Err(e) => {
+            eprintln!("Error finding data: {}", e);
+            Err(warp::reject::custom(e))
+        }
+    }
+}
+
+// Search book records in back-end DB for words in their title or author, most relevant first
+//
+async fn search_books_list(
+    search_query: SearchQuery, causal: CausalContext, books_mgr: Arc<dyn BooksStore>,
 ) -> Result<impl warp::Reply, warp::Rejection> {
-    match books_mgr.db_find_books(&book_payload_to_book(&book_payload)).await {
-        Ok(result) => Ok(warp::reply::json(&books_to_books_payload(&result))),
+    let text = search_query.q.as_deref().map(str::trim).filter(|text| !text.is_empty());
+    let text =
+        text.ok_or_else(|| warp::reject::custom(AppError::MissingField(String::from("q"))))?;
+    let limit = validate_limit(search_query.limit).map_err(warp::reject::custom)?;
+
+    match books_mgr.db_search_books(text, limit, &causal).await {
+        Ok(results) => Ok(causal_reply(
+            warp::reply::json(&search_results_to_search_payload(&results)),
+            &causal,
+        )),
         Err(e) => {
-            eprintln!("Error deleting data: {}", e.to_string());
-            Err(warp::reject())
+            eprintln!("Error searching data: {}", e);
+            Err(warp::reject::custom(e))
         }
     }
 }
 
-// Delete specific book record from back-end DB
+// Stream all matching book records from back-end DB into a chunked response body, so the response
+// is sent whilst the books are still being read, narrowing each book to the requested fields
+//
+async fn stream_books_list(
+    filter: &BooksFilter, fields: &FieldSelection, projected: &FieldSelection, sort: &SortOrder,
+    causal: &CausalContext, books_mgr: Arc<dyn BooksStore>, format: StreamFormat,
+) -> Result<warp::reply::Response, warp::Rejection> {
+    match books_mgr.db_stream_books(filter, projected, sort, causal).await {
+        Ok(books) => {
+            let fields = fields.clone();
+            let books_payload = books
+                .map(move |book| book.and_then(|book| fields.narrow(&book_to_book_payload(&book))));
+            Ok(causal_reply(streamed_json_response(books_payload, format), causal))
+        }
+        Err(e) => {
+            eprintln!("Error finding data: {}", e);
+            Err(warp::reject::custom(e))
+        }
+    }
+}
+
+// Delete specific book record from back-end DB, identified by the payload's title & author, if
+// still at a version the request requires
 //
 async fn delete_book_list(
-    book_payload: BookPayload, books_mgr: BooksMgr,
+    book_payload: BookPayload, if_match: Option<String>, causal: CausalContext,
+    books_mgr: Arc<dyn BooksStore>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let key = book_payload_to_key(&book_payload).map_err(warp::reject::custom)?;
+    delete_book(&key.at_versions(if_match_versions(if_match.as_deref())), &causal, books_mgr).await
+}
+
+// Find the book record with the id from back-end DB, only returning the requested fields, along
+// with the entity tag of the book's version, unless the client already has the same version
+//
+async fn get_book_item(
+    id: String, book_query: BookQuery, conditions: ReadConditions, causal: CausalContext,
+    books_mgr: Arc<dyn BooksStore>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let key = BookKey::from_id(&id).map_err(warp::reject::custom)?;
+    let fields = book_query.fields.as_deref();
+    let fields = fields.map(|fields| FieldSelection::parse(fields, OWNED_FIELDS)).transpose();
+    let fields =
+        fields.map_err(warp::reject::custom)?.unwrap_or_else(|| FieldSelection::all(OWNED_FIELDS));
+    let mut projected = fields.clone();
+    projected.add("last_modified");
+    projected.add("version");
+
+    match books_mgr.db_find_book(&key, &projected, &causal).await {
+        Ok(Some(book)) => {
+            let payload =
+                fields.narrow(&book_to_book_payload(&book)).map_err(warp::reject::custom)?;
+            let version = Version::of_book(book.version, book.last_modified);
+            let reply = versioned_reply(warp::reply::json(&payload), Some(&version), &conditions);
+            Ok(causal_reply(reply, &causal))
+        }
+        Ok(None) => Err(book_not_found(&key)),
+        Err(e) => {
+            eprintln!("Error finding data: {}", e);
+            Err(warp::reject::custom(e))
+        }
+    }
+}
+
+// Replace all the fields clients can change of the book record with the id in back-end DB, if
+// still at a version the request requires
+//
+async fn replace_book_item(
+    id: String, book_payload: BookPayload, if_match: Option<String>, causal: CausalContext,
+    books_mgr: Arc<dyn BooksStore>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let key = BookKey::from_id(&id).map_err(warp::reject::custom)?;
+    let key = key.at_versions(if_match_versions(if_match.as_deref()));
+
+    match books_mgr.db_replace_book(&key, &book_payload_to_book(&book_payload), &causal).await {
+        Ok(counts) if counts.is_unmatched() => Err(unmatched_book(&key, &causal, &books_mgr).await),
+        Ok(_) => Ok(causal_reply(
+            warp::reply::with_status("Replaced book in the book list", http::StatusCode::OK),
+            &causal,
+        )),
+        Err(e) => {
+            eprintln!("Error updating data: {}", e);
+            Err(warp::reject::custom(e))
+        }
+    }
+}
+
+// Change just the provided fields of the book record with the id in back-end DB, if still at a
+// version the request requires
+//
+async fn patch_book_item(
+    id: String, book_payload: BookPayload, if_match: Option<String>, causal: CausalContext,
+    books_mgr: Arc<dyn BooksStore>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let key = BookKey::from_id(&id).map_err(warp::reject::custom)?;
+    let key = key.at_versions(if_match_versions(if_match.as_deref()));
+
+    match books_mgr.db_patch_book(&key, &book_payload_to_book(&book_payload), &causal).await {
+        Ok(counts) if counts.is_unmatched() => Err(unmatched_book(&key, &causal, &books_mgr).await),
+        Ok(_) => Ok(causal_reply(
+            warp::reply::with_status("Updated book in the book list", http::StatusCode::OK),
+            &causal,
+        )),
+        Err(e) => {
+            eprintln!("Error updating data: {}", e);
+            Err(warp::reject::custom(e))
+        }
+    }
+}
+
+// Delete the book record with the id from back-end DB, if still at a version the request requires
+//
+async fn delete_book_item(
+    id: String, if_match: Option<String>, causal: CausalContext, books_mgr: Arc<dyn BooksStore>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let key = BookKey::from_id(&id).map_err(warp::reject::custom)?;
+    delete_book(&key.at_versions(if_match_versions(if_match.as_deref())), &causal, books_mgr).await
+}
+
+// Delete the book record with the key from back-end DB
+//
+async fn delete_book(
+    key: &BookKey, causal: &CausalContext, books_mgr: Arc<dyn BooksStore>,
 ) -> Result<impl warp::Reply, warp::Rejection> {
-    match books_mgr.db_delete_book(&book_payload_to_book(&book_payload)).await {
-        Ok(_) => Ok(warp::reply::with_status("Removed book from books list", http::StatusCode::OK)),
+    match books_mgr.db_delete_book(key, causal).await {
+        Ok(counts) if counts.is_unmatched() => Err(unmatched_book(key, causal, &books_mgr).await),
+        Ok(_) => Ok(causal_reply(
+            warp::reply::with_status(warp::reply(), http::StatusCode::NO_CONTENT),
+            causal,
+        )),
+        Err(e) => {
+            eprintln!("Error deleting data: {}", e);
+            Err(warp::reject::custom(e))
+        }
+    }
+}
+
+// Build rejection for a request targeting a book which is not in the book list
+//
+fn book_not_found(key: &BookKey) -> warp::Rejection {
+    warp::reject::custom(AppError::NotFound(format!("No book {} in the book list", key)))
+}
+
+// Build rejection for a write which matched no book, reporting whether the book is in the book
+// list but no longer at a version the request requires, or is not in the book list at all
+//
+async fn unmatched_book(
+    key: &BookKey, causal: &CausalContext, books_mgr: &Arc<dyn BooksStore>,
+) -> warp::Rejection {
+    if !key.has_versions() {
+        return book_not_found(key);
+    }
+
+    let fields = FieldSelection::all(&["version"]);
+
+    match books_mgr.db_find_book(&key.any_version(), &fields, causal).await {
+        Ok(Some(book)) if !key.matches_version(book.version) => {
+            warp::reject::custom(AppError::PreconditionFailed(format!(
+                "Book {} has been modified since the version the request requires",
+                key
+            )))
+        }
+        Ok(_) => book_not_found(key),
         Err(e) => {
-            eprintln!("Error deleting data: {}", e.to_string());
-            Err(warp::reject())
+            eprintln!("Error finding data: {}", e);
+            warp::reject::custom(e)
         }
     }
 }
 
+// Build path of the REST API resource for a book
+//
+fn book_location(book: &Book) -> String {
+    let id = book.id.map(|id| id.to_hex()).unwrap_or_default();
+    format!("/{}/{}/{}", RSC_VERSION, RSC_NAME, id)
+}
+
+// Take title & author of Book payload and put into the key identifying the book to the DB tier,
+// for requests which predate book ids
+//
+fn book_payload_to_key(book_payload: &BookPayload) -> Result<BookKey, AppError> {
+    BookKey::from_title_author(book_payload.title.as_ref(), book_payload.author.as_ref())
+}
+
 // Take contents of Book payload and put into Book record to be passed to DB tier
 //
 fn book_payload_to_book(book_payload: &BookPayload) -> Book {
     Book {
+        id: None,
         title: book_payload.title.clone(),
         author: book_payload.author.clone(),
         year: book_payload.year,
@@ -147,23 +631,513 @@ fn book_payload_to_book(book_payload: &BookPayload) -> Book {
         explicit: book_payload.explicit,
         first_created: None,
         last_modified: None,
+        version: None,
     }
 }
 
+// Fields to read from the DB tier for a listing, being the requested fields plus the fields the
+// listing is sorted by, which are needed to build the cursor for the next page, and the time of
+// last modification, which versions the listing
+//
+fn projected_fields(fields: &FieldSelection, sort: &SortOrder) -> FieldSelection {
+    let mut projected = fields.clone();
+    projected.add("last_modified");
+
+    for key in &sort.keys {
+        projected.add(key.field.name());
+    }
+
+    projected
+}
+
+// Latest time of last modification of the listed books, if any
+//
+fn latest_modification(books: &[Book]) -> Option<DateTime> {
+    books.iter().filter_map(|book| book.last_modified).max()
+}
+
+// Take filter fields of the query string and put into Books filter to be passed to DB tier,
+// returning an error if any of the fields are invalid
+//
+fn books_query_to_filter(books_query: &BooksQuery) -> Result<BooksFilter, AppError> {
+    let ignore_case = books_query.ignore_case.unwrap_or_default();
+    Ok(BooksFilter {
+        title: BooksFilter::text_match(
+            "title",
+            books_query.title.as_ref(),
+            books_query.title_prefix.as_ref(),
+            ignore_case,
+        )?,
+        author: BooksFilter::text_match(
+            "author",
+            books_query.author.as_ref(),
+            books_query.author_prefix.as_ref(),
+            ignore_case,
+        )?,
+        year_gte: books_query.year_gte,
+        year_lt: books_query.year_lt,
+        quantity_gte: books_query.quantity_gte,
+        quantity_lt: books_query.quantity_lt,
+        explicit: books_query.explicit,
+        first_created_since: BooksFilter::timestamp(
+            "first_created_since",
+            books_query.first_created_since.as_ref(),
+        )?,
+        first_created_before: BooksFilter::timestamp(
+            "first_created_before",
+            books_query.first_created_before.as_ref(),
+        )?,
+        last_modified_since: BooksFilter::timestamp(
+            "last_modified_since",
+            books_query.last_modified_since.as_ref(),
+        )?,
+        last_modified_before: BooksFilter::timestamp(
+            "last_modified_before",
+            books_query.last_modified_before.as_ref(),
+        )?,
+    })
+}
+
+// Build response page of Books payload based on page of book records returned from DB tier, where
+// the link to the next page repeats the query but with the cursor replacing any skip
+//
+fn books_page_to_books_page_payload(
+    books_page: &BooksPage, fields: &FieldSelection, books_query: &BooksQuery,
+) -> Result<BooksPagePayload, AppError> {
+    let next = match &books_page.next {
+        Some(cursor) => {
+            let next_query =
+                BooksQuery { skip: None, cursor: Some(cursor.encode()?), ..books_query.clone() };
+            let query_string = serde_urlencoded::to_string(&next_query)
+                .map_err(|e| AppError::Internal(e.to_string()))?;
+            Some(format!("/{}/{}?{}", RSC_VERSION, RSC_NAME, query_string))
+        }
+        None => None,
+    };
+
+    Ok(BooksPagePayload {
+        books: fields.narrow(&books_to_books_payload(&books_page.books))?,
+        next,
+        total: books_page.total,
+    })
+}
+
 // Build response Books payload based on book records returned from DB tier
 //
 fn books_to_books_payload(books: &[Book]) -> Vec<BookPayload> {
     let mut books_payload = vec![];
 
     for book in books {
-        books_payload.push(BookPayload {
-            title: book.title.clone(),
-            author: book.author.clone(),
-            year: book.year,
-            quantity: book.quantity,
-            explicit: book.explicit,
-        });
+        books_payload.push(book_to_book_payload(book));
     }
 
     books_payload
 }
+
+// Build response Book payload based on a book record returned from DB tier
+//
+fn book_to_book_payload(book: &Book) -> BookPayload {
+    BookPayload {
+        id: book.id.map(|id| id.to_hex()),
+        title: book.title.clone(),
+        author: book.author.clone(),
+        year: book.year,
+        quantity: book.quantity,
+        explicit: book.explicit,
+        first_created: book.first_created.map(|time| time.to_rfc3339_string()),
+        last_modified: book.last_modified.map(|time| time.to_rfc3339_string()),
+    }
+}
+
+// Build response Book search payload based on search results returned from DB tier
+//
+fn search_results_to_search_payload(results: &[BookSearchResult]) -> Vec<BookSearchPayload> {
+    results
+        .iter()
+        .map(|result| BookSearchPayload {
+            id: result.book.id.map(|id| id.to_hex()),
+            title: result.book.title.clone(),
+            author: result.book.author.clone(),
+            year: result.book.year,
+            quantity: result.book.quantity,
+            explicit: result.book.explicit,
+            first_created: result.book.first_created.map(|time| time.to_rfc3339_string()),
+            last_modified: result.book.last_modified.map(|time| time.to_rfc3339_string()),
+            relevance: result.relevance,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mem_store::MemCollection;
+    use serde_json::{json, Value};
+    use warp::http::StatusCode;
+
+    const URL: &str = "/v1/books";
+
+    // Routes of app1 over a new, empty in-memory books collection
+    //
+    fn mem_routes() -> impl Filter<Extract = (impl warp::Reply,), Error = Infallible> + Clone {
+        books_routes(Arc::new(MemBooksMgr::new(MemCollection::default())), 16384)
+    }
+
+    // Add a new book via the routes, returning its location
+    //
+    async fn add_book(
+        routes: &(impl Filter<Extract = (impl warp::Reply,), Error = Infallible> + Clone + 'static),
+        title: &str, year: i32,
+    ) -> String {
+        let response = warp::test::request()
+            .method("POST")
+            .path(URL)
+            .json(&json!({"title": title, "author": "Test Writer", "year": year, "quantity": 3}))
+            .reply(routes)
+            .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        response.headers()[http::header::LOCATION].to_str().unwrap().to_string()
+    }
+
+    // Read the JSON body of a GET of the path via the routes, asserting it succeeds
+    //
+    async fn get_json(
+        routes: &(impl Filter<Extract = (impl warp::Reply,), Error = Infallible> + Clone + 'static),
+        path: &str,
+    ) -> Value {
+        let response = warp::test::request().path(path).reply(routes).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        serde_json::from_slice(response.body()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn new_book_listed_and_read_by_its_location() {
+        let routes = mem_routes();
+        let location = add_book(&routes, "Test Book", 2020).await;
+        let books =
+            get_json(&routes, &format!("{}?title=Test%20Book&author=Test%20Writer", URL)).await;
+        assert_eq!(books.as_array().unwrap().len(), 1);
+        assert_eq!(books[0]["year"], 2020);
+        let book = get_json(&routes, &format!("{}?fields=title,quantity", location)).await;
+        assert_eq!(book, json!({"id": books[0]["id"], "title": "Test Book", "quantity": 3}));
+    }
+
+    #[tokio::test]
+    async fn book_replaced_and_changed_by_its_location() {
+        let routes = mem_routes();
+        let location = add_book(&routes, "Test Book", 2020).await;
+        let book =
+            json!({"title": "Test Book", "author": "Test Writer", "year": 2021, "quantity": 7});
+        let response =
+            warp::test::request().method("PUT").path(&location).json(&book).reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = warp::test::request()
+            .method("PATCH")
+            .path(&location)
+            .json(&json!({"quantity": 9}))
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let book = get_json(&routes, &location).await;
+        assert_eq!((&book["year"], &book["quantity"]), (&json!(2021), &json!(9)));
+
+        for (path, status) in [
+            (format!("{}/{}", URL, bson::oid::ObjectId::new().to_hex()), StatusCode::NOT_FOUND),
+            (format!("{}/not-an-id", URL), StatusCode::BAD_REQUEST),
+        ] {
+            let response = warp::test::request()
+                .method("PATCH")
+                .path(&path)
+                .json(&json!({"quantity": 9}))
+                .reply(&routes)
+                .await;
+            assert_eq!(response.status(), status, "path: {}", path);
+        }
+    }
+
+    #[tokio::test]
+    async fn new_book_found_by_its_timestamps() {
+        let routes = mem_routes();
+        add_book(&routes, "Test Book", 2020).await;
+        let books = get_json(&routes, &format!("{}?fields=first_created,last_modified", URL)).await;
+        let created = books[0]["first_created"].as_str().unwrap().to_string();
+        assert_eq!(books[0]["last_modified"], created.as_str());
+        assert!(bson::DateTime::parse_rfc3339_str(&created).is_ok());
+
+        for (query, count) in [
+            (format!("first_created_since={}&last_modified_since={}", created, created), 1),
+            (format!("first_created_before={}", created), 0),
+            (format!("last_modified_before={}", created), 0),
+        ] {
+            let books = get_json(&routes, &format!("{}?{}", URL, query)).await;
+            assert_eq!(books.as_array().unwrap().len(), count, "query: {}", query);
+        }
+    }
+
+    #[tokio::test]
+    async fn change_only_applied_at_current_etag() {
+        let routes = mem_routes();
+        let location = add_book(&routes, "Test Book", 2020).await;
+        let response = warp::test::request().path(&location).reply(&routes).await;
+        let etag = response.headers()[http::header::ETAG].to_str().unwrap().to_string();
+        let patch = |if_match: &str| {
+            warp::test::request()
+                .method("PATCH")
+                .path(&location)
+                .header(http::header::IF_MATCH, if_match)
+                .json(&json!({"year": 2021}))
+        };
+        let response = patch("\"0\"").reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+        let response = patch(&etag).reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = patch(&etag).reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+        assert_eq!(get_json(&routes, &location).await["year"], 2021);
+    }
+
+    #[tokio::test]
+    async fn unchanged_book_not_resent() {
+        let routes = mem_routes();
+        let location = add_book(&routes, "Test Book", 2020).await;
+        let response = warp::test::request().path(&location).reply(&routes).await;
+        let etag = response.headers()[http::header::ETAG].to_str().unwrap().to_string();
+        let last_modified = response.headers()[http::header::LAST_MODIFIED].to_str().unwrap();
+        let response = warp::test::request()
+            .path(&location)
+            .header(http::header::IF_NONE_MATCH, &etag)
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert!(response.body().is_empty());
+        let response = warp::test::request()
+            .path(&location)
+            .header(http::header::IF_MODIFIED_SINCE, last_modified)
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        let response = warp::test::request()
+            .method("PATCH")
+            .path(&location)
+            .json(&json!({"year": 2021}))
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = warp::test::request()
+            .path(&location)
+            .header(http::header::IF_NONE_MATCH, &etag)
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn listing_resent_after_book_removed() {
+        let routes = mem_routes();
+        let location = add_book(&routes, "Book A", 2001).await;
+        add_book(&routes, "Book B", 2002).await;
+        let response = warp::test::request().path(URL).reply(&routes).await;
+        let etag = response.headers()[http::header::ETAG].to_str().unwrap().to_string();
+        let listing = || warp::test::request().path(URL).header(http::header::IF_NONE_MATCH, &etag);
+        assert_eq!(listing().reply(&routes).await.status(), StatusCode::NOT_MODIFIED);
+        warp::test::request().method("DELETE").path(&location).reply(&routes).await;
+        assert_eq!(listing().reply(&routes).await.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn invalid_causal_token_rejected() {
+        let response = warp::test::request()
+            .path(URL)
+            .header(crate::causal::CAUSAL_TOKEN_HEADER, "not-a-token")
+            .reply(&mem_routes())
+            .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body: Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["field"], "x-causal-token");
+    }
+
+    #[tokio::test]
+    async fn pages_followed_by_next_links() {
+        let routes = mem_routes();
+
+        for (title, year) in [("Book A", 2001), ("Book B", 2002), ("Book C", 2003)] {
+            add_book(&routes, title, year).await;
+        }
+
+        let mut path = format!("{}?sort=-year&fields=title&limit=2&count=true", URL);
+        let mut titles = vec![];
+
+        loop {
+            let page = get_json(&routes, &path).await;
+            assert_eq!(page["total"], 3);
+
+            for book in page["books"].as_array().unwrap() {
+                assert_eq!(book.as_object().unwrap().keys().collect::<Vec<_>>(), ["id", "title"]);
+                titles.push(book["title"].as_str().unwrap().to_string());
+            }
+
+            match page["next"].as_str() {
+                Some(next) => path = next.to_string(),
+                None => break,
+            }
+        }
+
+        assert_eq!(titles, ["Book C", "Book B", "Book A"]);
+    }
+
+    #[tokio::test]
+    async fn books_streamed_as_json_array_or_ndjson() {
+        let routes = mem_routes();
+
+        for count in 0..4 {
+            if count > 0 {
+                add_book(&routes, &format!("Book {}", count), 2000 + count).await;
+            }
+
+            let path = format!("{}?fields=title,year&stream=json", URL);
+            let response = warp::test::request().path(&path).reply(&routes).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()[http::header::CONTENT_TYPE], "application/json");
+            let books: Value = serde_json::from_slice(response.body()).unwrap();
+            assert_eq!(books.as_array().unwrap().len(), count as usize);
+            assert_eq!(books, get_json(&routes, &format!("{}?fields=title,year", URL)).await);
+
+            let path = format!("{}?fields=title,year&stream=ndjson", URL);
+            let response = warp::test::request().path(&path).reply(&routes).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()[http::header::CONTENT_TYPE], "application/x-ndjson");
+            let body = std::str::from_utf8(response.body()).unwrap();
+            assert!(body.is_empty() || body.ends_with('\n'));
+            let lines: Vec<Value> =
+                body.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+            assert_eq!(Value::from(lines), books);
+        }
+
+        let response =
+            warp::test::request().path(&format!("{}?stream=csv", URL)).reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn search_ranks_title_matches_above_author_matches() {
+        let routes = mem_routes();
+        add_book(&routes, "The Kraken Wakes", 1953).await;
+        add_book(&routes, "The Writer's Kraken", 1960).await;
+        add_book(&routes, "The Chrysalids", 1955).await;
+        let results = get_json(&routes, &format!("{}/search?q=writer", URL)).await;
+        let titles: Vec<&str> = results
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|book| book["title"].as_str().unwrap())
+            .collect();
+        assert_eq!(titles[0], "The Writer's Kraken");
+        assert_eq!(titles.len(), 3);
+        let results = get_json(&routes, &format!("{}/search?q=kraken&limit=1", URL)).await;
+        assert_eq!(results.as_array().unwrap().len(), 1);
+        let response =
+            warp::test::request().path(&format!("{}/search?q=%20", URL)).reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn invalid_cursor_rejected() {
+        let response = warp::test::request()
+            .path(&format!("{}?limit=1&cursor=not-a-cursor", URL))
+            .reply(&mem_routes())
+            .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let error: Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(error["field"], "cursor");
+    }
+
+    #[tokio::test]
+    async fn unknown_sort_field_rejected() {
+        let response = warp::test::request()
+            .path(&format!("{}?sort=year,-publisher", URL))
+            .reply(&mem_routes())
+            .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let error: Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(error["field"], "sort");
+    }
+
+    #[tokio::test]
+    async fn books_listed_by_filter() {
+        let routes = mem_routes();
+
+        for (title, year) in [("C++ Primer", 2005), ("CCC", 2010), ("c++ for kids", 2015)] {
+            add_book(&routes, title, year).await;
+        }
+
+        for (query, expected) in [
+            ("title_prefix=C%2B%2B", vec!["C++ Primer"]),
+            ("title_prefix=c%2B%2B&ignore_case=true", vec!["C++ Primer", "c++ for kids"]),
+            ("title=ccc&ignore_case=true", vec!["CCC"]),
+            ("title_prefix=.*", vec![]),
+            ("year_gte=2005&year_lt=2015&author=Test%20Writer", vec!["C++ Primer", "CCC"]),
+            ("year_gte=2010&quantity_lt=3", vec![]),
+        ] {
+            let books = get_json(&routes, &format!("{}?{}", URL, query)).await;
+            let titles: Vec<&str> = books
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|book| book["title"].as_str().unwrap())
+                .collect();
+            assert_eq!(titles, expected, "query: {}", query);
+        }
+
+        for (query, field) in [
+            ("title=CCC&title_prefix=C", "title_prefix"),
+            ("last_modified_since=yesterday", "last_modified_since"),
+        ] {
+            let response =
+                warp::test::request().path(&format!("{}?{}", URL, query)).reply(&routes).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            let error: Value = serde_json::from_slice(response.body()).unwrap();
+            assert_eq!(error["field"], field);
+        }
+    }
+
+    #[tokio::test]
+    async fn duplicate_book_rejected() {
+        let routes = mem_routes();
+        add_book(&routes, "Test Book", 2020).await;
+        let response = warp::test::request()
+            .method("POST")
+            .path(URL)
+            .json(&json!({"title": "Test Book", "author": "Test Writer", "year": 1, "quantity": 1}))
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn quantity_added_and_book_removed() {
+        let routes = mem_routes();
+        add_book(&routes, "Test Book", 2020).await;
+        let book = json!({"title": "Test Book", "author": "Test Writer", "quantity": 2});
+        let response =
+            warp::test::request().method("PUT").path(URL).json(&book).reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(get_json(&routes, URL).await[0]["quantity"], 5);
+        let response =
+            warp::test::request().method("DELETE").path(URL).json(&book).reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(get_json(&routes, URL).await, json!([]));
+        let response =
+            warp::test::request().method("DELETE").path(URL).json(&book).reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn removed_book_not_found() {
+        let routes = mem_routes();
+        let location = add_book(&routes, "Test Book", 2020).await;
+        let response = warp::test::request().method("DELETE").path(&location).reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let response = warp::test::request().path(&location).reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,181 @@
+use bson::{doc, oid::ObjectId, Bson};
+
+use super::sorting::SortOrder;
+use crate::error::AppError;
+use crate::token::{decode_token, encode_token};
+
+pub const DEFAULT_PAGE_LIMIT: u32 = 20;
+pub const MAX_PAGE_LIMIT: u32 = 1000;
+
+// Position in a books listing after which the next page starts, being the last book's values for
+// the listing's sort fields and its id, along with the sort order the values relate to
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageCursor {
+    pub sort: String,
+    pub values: Vec<Bson>,
+    pub id: ObjectId,
+}
+
+impl PageCursor {
+    // Encode as an opaque token for clients to pass back when requesting the next page
+    //
+    pub fn encode(&self) -> Result<String, AppError> {
+        encode_token(&doc! {"sort": &self.sort, "values": self.values.clone(), "id": self.id})
+    }
+
+    // Decode a token previously provided to a client, returning an error naming the query string
+    // field if the token is not valid
+    //
+    pub fn decode(token: &str) -> Result<Self, AppError> {
+        Self::try_decode(token).ok_or_else(invalid_cursor_error)
+    }
+
+    // Decode a token, returning nothing if the token is not valid
+    //
+    fn try_decode(token: &str) -> Option<Self> {
+        let doc = decode_token(token)?;
+        Some(Self {
+            sort: doc.get_str("sort").ok()?.to_string(),
+            values: doc.get_array("values").ok()?.clone(),
+            id: doc.get_object_id("id").ok()?,
+        })
+    }
+}
+
+// Which page of the books listing to return, where no limit means return all remaining books
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PageRequest {
+    pub limit: Option<u32>,
+    pub skip: Option<u64>,
+    pub after: Option<PageCursor>,
+    pub count_total: bool,
+}
+
+impl PageRequest {
+    // Build page request from the query string's paging fields, using the default limit if only
+    // other paging fields are provided, and returning an error if any value is unacceptable,
+    // including a cursor from a listing with a different sort order, or with a value which isn't
+    // of its sort field's type (so a forged cursor can't inject query operators into the filter)
+    //
+    pub fn new(
+        limit: Option<u32>, skip: Option<u64>, cursor: Option<&str>, count_total: bool,
+        sort: &SortOrder,
+    ) -> Result<Self, AppError> {
+        let limit = validate_limit(limit)?;
+        let after = cursor.map(PageCursor::decode).transpose()?;
+
+        if let Some(after) = &after {
+            if after.sort != sort.to_string() || after.values.len() != sort.keys.len() {
+                return Err(AppError::InvalidField {
+                    field: String::from("cursor"),
+                    reason: format!("does not match the sort order `{}`", sort),
+                });
+            }
+
+            if !sort.keys.iter().zip(&after.values).all(|(key, value)| key.field.accepts(value)) {
+                return Err(invalid_cursor_error());
+            }
+        }
+
+        Ok(Self { limit: Some(limit), skip, after, count_total })
+    }
+}
+
+// Build error for a query string cursor which wasn't returned by a previous request
+//
+fn invalid_cursor_error() -> AppError {
+    AppError::InvalidField {
+        field: String::from("cursor"),
+        reason: String::from("is not a cursor returned by a previous request"),
+    }
+}
+
+// Check the query string's limit on the number of books to return is acceptable, returning the
+// limit to apply, which is the default limit if none is provided
+//
+pub fn validate_limit(limit: Option<u32>) -> Result<u32, AppError> {
+    let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+
+    if limit == 0 || limit > MAX_PAGE_LIMIT {
+        return Err(AppError::InvalidField {
+            field: String::from("limit"),
+            reason: format!("must be between 1 and {}", MAX_PAGE_LIMIT),
+        });
+    }
+
+    Ok(limit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bson::DateTime;
+
+    // Page request for the sort order, following the cursor with the values
+    //
+    fn request_after(
+        sort: &str, cursor_sort: &str, values: Vec<Bson>,
+    ) -> Result<PageRequest, AppError> {
+        let cursor =
+            PageCursor { sort: cursor_sort.to_string(), values, id: ObjectId::new() }.encode()?;
+        PageRequest::new(Some(5), None, Some(&cursor), false, &SortOrder::parse(sort)?)
+    }
+
+    // Reason given for rejecting the query string field's value
+    //
+    fn rejection(result: Result<PageRequest, AppError>) -> (String, String) {
+        match result {
+            Err(AppError::InvalidField { field, reason }) => (field, reason),
+            other => panic!("Expected invalid field error, not {:?}", other),
+        }
+    }
+
+    #[test]
+    fn cursor_round_trips() {
+        let values = vec![Bson::from("Writer"), Bson::Null];
+        let cursor = PageCursor { sort: String::from("author,-year"), values, id: ObjectId::new() };
+        assert_eq!(PageCursor::decode(&cursor.encode().unwrap()).unwrap(), cursor);
+        assert!(PageCursor::decode("not-a-cursor").is_err());
+    }
+
+    #[test]
+    fn cursor_values_of_sort_field_types_accepted() {
+        let values = vec![Bson::from("Writer"), Bson::Int32(2001)];
+        let page = request_after("author,-year", "author,-year", values.clone()).unwrap();
+        assert_eq!(page.after.unwrap().values, values);
+        let values = vec![Bson::Null, Bson::DateTime(DateTime::now())];
+        assert!(request_after("title,last_modified", "title,last_modified", values).is_ok());
+    }
+
+    #[test]
+    fn cursor_of_other_sort_order_rejected() {
+        let (field, reason) = rejection(request_after("-year", "year", vec![Bson::Int32(2001)]));
+        assert_eq!(field, "cursor");
+        assert!(reason.contains("sort order `-year`"));
+        let values = vec![Bson::Int32(2001), Bson::Int32(2001)];
+        assert!(rejection(request_after("year", "year", values)).1.contains("sort order"));
+    }
+
+    #[test]
+    fn cursor_values_of_other_types_rejected() {
+        let injection = Bson::Document(doc! {"$gt": ""});
+
+        for value in [Bson::from("2001"), Bson::Int64(2001), Bson::Double(2001.0), injection] {
+            let (field, reason) = rejection(request_after("year", "year", vec![value]));
+            assert_eq!(field, "cursor");
+            assert!(reason.contains("not a cursor"));
+        }
+
+        let values = vec![Bson::Int32(1)];
+        let (field, _) = rejection(request_after("last_modified", "last_modified", values));
+        assert_eq!(field, "cursor");
+    }
+
+    #[test]
+    fn limit_defaulted_and_bounded() {
+        assert_eq!(validate_limit(None).unwrap(), DEFAULT_PAGE_LIMIT);
+        assert_eq!(validate_limit(Some(MAX_PAGE_LIMIT)).unwrap(), MAX_PAGE_LIMIT);
+        assert!(validate_limit(Some(0)).is_err());
+        assert!(validate_limit(Some(MAX_PAGE_LIMIT + 1)).is_err());
+    }
+}
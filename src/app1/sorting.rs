@@ -0,0 +1,182 @@
+use bson::Bson;
+use std::fmt;
+
+use crate::error::AppError;
+
+// Book fields which listings may be sorted by, restricted to the fields which are indexed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortField {
+    Year,
+    Title,
+    Author,
+    Quantity,
+    LastModified,
+}
+
+impl SortField {
+    // Name of the field in book records & query strings
+    //
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Year => "year",
+            Self::Title => "title",
+            Self::Author => "author",
+            Self::Quantity => "quantity",
+            Self::LastModified => "last_modified",
+        }
+    }
+
+    // Check a value has the type which a book's value for the field has in a page cursor, where
+    // a book without a value for the field has a null value
+    //
+    pub fn accepts(&self, value: &Bson) -> bool {
+        matches!(
+            (self, value),
+            (_, Bson::Null)
+                | (Self::Year | Self::Quantity, Bson::Int32(_))
+                | (Self::Title | Self::Author, Bson::String(_))
+                | (Self::LastModified, Bson::DateTime(_))
+        )
+    }
+
+    // Find the sortable field with the given name
+    //
+    fn from_name(name: &str) -> Option<Self> {
+        [Self::Year, Self::Title, Self::Author, Self::Quantity, Self::LastModified]
+            .into_iter()
+            .find(|field| field.name() == name)
+    }
+}
+
+// Single field of a sort order and its direction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SortKey {
+    pub field: SortField,
+    pub descending: bool,
+}
+
+// Order to list books in, by one or more fields, where books with equal values for all the fields
+// are always then ordered by id, so that every book has a fixed position in the order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortOrder {
+    pub keys: Vec<SortKey>,
+}
+
+impl Default for SortOrder {
+    // Order by year, ascending
+    //
+    fn default() -> Self {
+        Self { keys: vec![SortKey { field: SortField::Year, descending: false }] }
+    }
+}
+
+impl SortOrder {
+    // Parse the value of a 'sort' query parameter, being a comma separated list of field names,
+    // each prefixed with '-' for descending order (eg. 'author,-year'), returning an error if any
+    // field is not sortable or is repeated
+    //
+    pub fn parse(sort: &str) -> Result<Self, AppError> {
+        let mut keys: Vec<SortKey> = vec![];
+
+        for name in sort.split(',') {
+            let (name, descending) = match name.strip_prefix('-') {
+                Some(name) => (name, true),
+                None => (name, false),
+            };
+            let field = SortField::from_name(name).ok_or_else(|| {
+                sort_error(format!(
+                    "`{}` is not one of the sortable fields: year, title, author, quantity, \
+                    last_modified",
+                    name
+                ))
+            })?;
+
+            if keys.iter().any(|key| key.field == field) {
+                return Err(sort_error(format!("`{}` is listed more than once", name)));
+            }
+
+            keys.push(SortKey { field, descending });
+        }
+
+        Ok(Self { keys })
+    }
+}
+
+impl fmt::Display for SortOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (pos, key) in self.keys.iter().enumerate() {
+            let separator = if pos > 0 { "," } else { "" };
+            let direction = if key.descending { "-" } else { "" };
+            write!(f, "{}{}{}", separator, direction, key.field.name())?;
+        }
+
+        Ok(())
+    }
+}
+
+// Build error for an unacceptable 'sort' query parameter
+//
+fn sort_error(reason: String) -> AppError {
+    AppError::InvalidField { field: String::from("sort"), reason }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Reason given for rejecting the sort order
+    //
+    fn rejection(sort: &str) -> String {
+        match SortOrder::parse(sort) {
+            Err(AppError::InvalidField { field, reason }) => {
+                assert_eq!(field, "sort");
+                reason
+            }
+            other => panic!("Expected invalid field error, not {:?}", other),
+        }
+    }
+
+    #[test]
+    fn sortable_fields_parsed_with_direction() {
+        let sort = SortOrder::parse("author,-year,quantity,-last_modified,title").unwrap();
+        let keys: Vec<(SortField, bool)> =
+            sort.keys.iter().map(|key| (key.field, key.descending)).collect();
+        assert_eq!(
+            keys,
+            [
+                (SortField::Author, false),
+                (SortField::Year, true),
+                (SortField::Quantity, false),
+                (SortField::LastModified, true),
+                (SortField::Title, false),
+            ]
+        );
+        assert_eq!(sort.to_string(), "author,-year,quantity,-last_modified,title");
+        assert_eq!(SortOrder::default().to_string(), "year");
+    }
+
+    #[test]
+    fn unsortable_fields_rejected() {
+        for name in ["explicit", "_id", "id", "scores", "Year", "", "--year", "year "] {
+            assert!(rejection(name).contains("not one of the sortable fields"), "field: {}", name);
+        }
+
+        assert!(rejection("year,").contains("``"));
+    }
+
+    #[test]
+    fn repeated_fields_rejected() {
+        assert!(rejection("year,-year").contains("`year` is listed more than once"));
+        assert!(rejection("title,author,title").contains("`title` is listed more than once"));
+    }
+
+    #[test]
+    fn cursor_values_checked_against_field_type() {
+        assert!(SortField::Year.accepts(&Bson::Int32(2001)));
+        assert!(SortField::Title.accepts(&Bson::Null));
+        assert!(SortField::LastModified.accepts(&Bson::DateTime(bson::DateTime::now())));
+        assert!(!SortField::Year.accepts(&Bson::String(String::from("2001"))));
+        assert!(!SortField::Author.accepts(&Bson::Int32(1)));
+        assert!(!SortField::Quantity.accepts(&Bson::Document(bson::doc! {"$gt": 0})));
+    }
+}
@@ -0,0 +1,81 @@
+use futures::{future, stream, Stream, StreamExt};
+use serde::Serialize;
+use warp::http::{header, Response};
+use warp::hyper::Body;
+
+use crate::error::AppError;
+
+const JSON_CONTENT_TYPE: &str = "application/json";
+const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+
+// Format of a response body which is streamed to the client as its records are read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamFormat {
+    // A single JSON array, the same as a buffered response
+    JsonArray,
+    // Newline delimited JSON, with one record per line
+    Ndjson,
+}
+
+impl StreamFormat {
+    // Parse the value of a 'stream' query parameter ('json' or 'ndjson')
+    //
+    pub fn parse(format: &str) -> Result<Self, AppError> {
+        match format {
+            "json" => Ok(Self::JsonArray),
+            "ndjson" => Ok(Self::Ndjson),
+            _ => Err(AppError::InvalidField {
+                field: String::from("stream"),
+                reason: String::from("must be 'json' or 'ndjson'"),
+            }),
+        }
+    }
+
+    // Value of the content type header for a response body of this format
+    //
+    fn content_type(&self) -> &'static str {
+        match self {
+            Self::JsonArray => JSON_CONTENT_TYPE,
+            Self::Ndjson => NDJSON_CONTENT_TYPE,
+        }
+    }
+}
+
+// Build a chunked response which serializes each record as it's pulled from the stream, so only one
+// record at a time is held in memory, where an error reading a record can only be reported to the
+// client by aborting the response part way through the body
+//
+pub fn streamed_json_response<S, T>(records: S, format: StreamFormat) -> Response<Body>
+where
+    S: Stream<Item = Result<T, AppError>> + Send + 'static,
+    T: Serialize,
+{
+    let chunks = records.enumerate().map(move |(pos, record)| {
+        let json =
+            serde_json::to_string(&record?).map_err(|e| AppError::Internal(e.to_string()))?;
+
+        Ok::<_, AppError>(match format {
+            StreamFormat::JsonArray if pos > 0 => format!(",{}", json),
+            StreamFormat::JsonArray => json,
+            StreamFormat::Ndjson => format!("{}\n", json),
+        })
+    });
+    let chunks = chunks.inspect(|chunk| {
+        if let Err(e) = chunk {
+            eprintln!("Error streaming data: {}", e);
+        }
+    });
+    let body = match format {
+        StreamFormat::JsonArray => Body::wrap_stream(
+            stream::once(future::ok(String::from("[")))
+                .chain(chunks)
+                .chain(stream::once(future::ok(String::from("]")))),
+        ),
+        StreamFormat::Ndjson => Body::wrap_stream(chunks),
+    };
+    let mut response = Response::new(body);
+    response
+        .headers_mut()
+        .insert(header::CONTENT_TYPE, header::HeaderValue::from_static(format.content_type()));
+    response
+}
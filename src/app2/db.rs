@@ -1,128 +1,546 @@
-use bson::DateTime;
+use async_trait::async_trait;
+use bson::{oid::ObjectId, Bson, DateTime, Document};
 use mongodb::{
     bson::doc,
-    options::FindOneOptions,
-    {Client, Collection},
+    options::{AggregateOptions, CollectionOptions, FindOneOptions, UpdateOptions},
+    {Client, ClientSession, Collection},
 };
-use serde::{Deserialize, Serialize};
-use std::error::Error;
+use serde::{Deserialize, Deserializer, Serialize};
 
-const DB_NAME: &str = "library";
-const COLL_NAME: &str = "books";
+use super::scoring::ScoreSort;
+use crate::causal::CausalContext;
+use crate::error::AppError;
+use crate::fields::FieldSelection;
+use crate::store::{BookKey, OperationSettings, WriteCounts};
+
+// Fields of the shared book records which app2 owns and returns to clients
+pub const OWNED_FIELDS: &[&str] = &["title", "author", "year", "scores", "last_modified"];
 
 // Book record
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Book {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub title: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub author: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub year: Option<i32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(
+        default,
+        deserialize_with = "deserialize_lenient_scores",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub scores: Option<Vec<Score>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub last_modified: Option<DateTime>,
+    // Count of writes to the record by either app, identifying its version
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<i64>,
 }
 
 // Score sub-record
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct Score {
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(
+        default,
+        deserialize_with = "deserialize_lenient_string",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub reference: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub rating: Option<i32>,
+    // Stored as a double, though legacy integer ratings are also read
+    #[serde(
+        default,
+        deserialize_with = "deserialize_lenient_rating",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub rating: Option<f64>,
+}
+
+// Summary of a book's valid review scores, as listed when browsing the scores of all books
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct BookScoresSummary {
+    #[serde(rename = "_id")]
+    pub id: Option<ObjectId>,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub year: Option<i32>,
+    pub average_score: Option<f64>,
+    pub scores_counted: u32,
+    pub last_modified: Option<DateTime>,
+}
+
+// Criteria for browsing the scores of all books, optionally only those by an author or published
+// in a year, ordered by average score (with books having no valid scores always last) and limited
+// to a maximum number of books
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BookScoresFilter {
+    pub author: Option<String>,
+    pub year: Option<i32>,
+    pub sort: Option<ScoreSort>,
+    pub limit: Option<u32>,
+}
+
+// Storage operations for book review scores, which the REST API handlers depend on so they can
+// run against either MongoDB or the in-memory store, where each operation observes & advances the
+// causal context of the request it's part of
+#[async_trait]
+pub trait BookScoresStore: Send + Sync {
+    // Query books returning list of book scores for the book with the key, where only the selected
+    // fields of the book need be read
+    async fn db_find_book_scores(
+        &self, key: &BookKey, fields: &FieldSelection, causal: &CausalContext,
+    ) -> Result<Option<Book>, AppError>;
+
+    // Summarize the valid scores of every book matching the filter, returning books ordered by
+    // title & author unless the filter specifies ordering by average score
+    async fn db_summarize_book_scores(
+        &self, filter: &BookScoresFilter, causal: &CausalContext,
+    ) -> Result<Vec<BookScoresSummary>, AppError>;
+
+    // Insert new book score for the book with the key, returning how many book records were
+    // affected, or a duplicate key error if the reviewer reference already has a score for the book
+    async fn db_insert_book_score(
+        &self, key: &BookKey, book: &Book, causal: &CausalContext,
+    ) -> Result<WriteCounts, AppError>;
+
+    // Atomically replace any existing book score for the matching reviewer reference (adding it if
+    // not yet present) for the book with the key, returning how many book records were affected
+    async fn db_update_book_score(
+        &self, key: &BookKey, book: &Book, causal: &CausalContext,
+    ) -> Result<WriteCounts, AppError>;
+
+    // Change the rating of the existing book score for the matching reviewer reference for the book
+    // with the key, returning how many book records had a score from the reviewer
+    async fn db_change_book_score(
+        &self, key: &BookKey, book: &Book, causal: &CausalContext,
+    ) -> Result<WriteCounts, AppError>;
+
+    // Delete a score from the record of the book with the key for the matching reviewer reference,
+    // returning how many book records had a score removed
+    async fn db_delete_book_scores(
+        &self, key: &BookKey, book: &Book, causal: &CausalContext,
+    ) -> Result<WriteCounts, AppError>;
 }
 
 // Book scores manager
 #[derive(Debug, Clone)]
 pub struct BookScoresMgr {
+    client: Client,
     coll: Collection<Book>,
+    settings: OperationSettings,
 }
 
-// Manages interaction with books database collection
-//
 impl BookScoresMgr {
-    // Create new instance of book score manager using provided MongoDB URL
+    // Create new instance of book score manager for the named collection, using provided MongoDB
+    // client (which may be shared with other apps), where the collection applies the write concern
+    // of the settings to each write and each read applies the read concern of its kind of operation
     //
-    pub async fn new(db_url: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
-        let client = Client::with_uri_str(db_url).await?;
-        let coll = client.database(DB_NAME).collection(COLL_NAME);
-        Ok(Self { coll })
+    pub fn new(
+        client: &Client, db_name: &str, coll_name: &str, settings: &OperationSettings,
+    ) -> Self {
+        let coll_options =
+            CollectionOptions::builder().write_concern(settings.write_concern.clone()).build();
+        let coll = client.database(db_name).collection_with_options(coll_name, coll_options);
+        Self { client: client.clone(), coll, settings: settings.clone() }
     }
 
-    // Query books collection returning list of book scores for a book
+    // Start the session for an operation which is part of a request with the causal context
     //
-    pub async fn db_find_book_scores(&self, book: &Book) -> Result<Option<Book>, Box<dyn Error>> {
-        if book.title.is_none() || book.author.is_none() {
-            return Ok(None);
-        }
+    async fn start_session(&self, causal: &CausalContext) -> Result<ClientSession, AppError> {
+        causal.start_session(&self.client, self.settings.causal_consistency).await
+    }
+
+    // Start the session for a read of a single book which is part of a request with the causal
+    // context, which isn't causally consistent if the read is linearizable
+    //
+    async fn start_book_read_session(
+        &self, causal: &CausalContext,
+    ) -> Result<ClientSession, AppError> {
+        causal.start_session(&self.client, self.settings.causal_book_reads()).await
+    }
 
-        let title = get_or_err(book.title.as_ref(), "title")?;
-        let author = get_or_err(book.author.as_ref(), "author")?;
+    // Check whether the book with the key exists, within the session of a write, only reading its
+    // id, with the read concern of reads of a single book, unless that's linearizable and the
+    // session causally consistent, which MongoDB doesn't support
+    //
+    async fn book_exists(
+        &self, key: &BookKey, session: &mut ClientSession,
+    ) -> Result<bool, AppError> {
+        let read_concern = if self.settings.causal_consistency && !self.settings.causal_book_reads()
+        {
+            None
+        } else {
+            self.settings.book_read_concern.clone()
+        };
+        let find_options = FindOneOptions::builder()
+            .projection(doc! {"_id": 1})
+            .read_concern(read_concern)
+            .build();
+        Ok(self.coll.find_one_with_session(key.filter(), find_options, session).await?.is_some())
+    }
+}
+
+// Manages interaction with books database collection
+//
+#[async_trait]
+impl BookScoresStore for BookScoresMgr {
+    // Query books collection returning list of book scores for a book
+    //
+    async fn db_find_book_scores(
+        &self, key: &BookKey, fields: &FieldSelection, causal: &CausalContext,
+    ) -> Result<Option<Book>, AppError> {
+        let mut session = self.start_book_read_session(causal).await?;
         let find_options = FindOneOptions::builder()
-            .projection(doc! {"title": 1, "author": 1, "year": 1, "scores": 1, "last_modified": 1})
+            .projection(fields.projection())
+            .read_concern(self.settings.book_read_concern.clone())
             .build();
-        let doc = self.coll.find_one(doc! {"title": title, "author": author}, find_options).await?;
+        let doc = self.coll.find_one_with_session(key.filter(), find_options, &mut session).await?;
+        causal.observe(&session);
         Ok(doc)
     }
 
-    // Insert new book score
+    // Summarize the scores of matching books using an aggregation pipeline, which only counts
+    // ratings that are finite numbers (matching how app2 reads individual scores)
+    //
+    async fn db_summarize_book_scores(
+        &self, filter: &BookScoresFilter, causal: &CausalContext,
+    ) -> Result<Vec<BookScoresSummary>, AppError> {
+        let mut match_doc = doc! {};
+
+        if let Some(author) = &filter.author {
+            match_doc.insert("author", author);
+        }
+
+        if let Some(year) = filter.year {
+            match_doc.insert("year", year);
+        }
+
+        let sort_doc = match filter.sort {
+            Some(sort) => doc! {
+                "has_scores": -1, "average_score": sort.direction(), "title": 1, "author": 1
+            },
+            None => doc! {"title": 1, "author": 1},
+        };
+        let mut pipeline = vec![
+            doc! {"$match": match_doc},
+            doc! {"$project": {
+                "title": 1,
+                "author": 1,
+                "year": 1,
+                "last_modified": 1,
+                "ratings": {"$filter": {
+                    "input": {"$map": {
+                        "input": {"$cond": [{"$isArray": "$scores"}, "$scores", []]},
+                        "in": "$$this.rating",
+                    }},
+                    "cond": {"$and": [
+                        {"$in": [{"$type": "$$this"}, ["int", "long", "double"]]},
+                        {"$not": [
+                            {"$in": ["$$this", [f64::NAN, f64::INFINITY, f64::NEG_INFINITY]]}
+                        ]},
+                    ]},
+                }},
+            }},
+            doc! {"$project": {
+                "title": 1,
+                "author": 1,
+                "year": 1,
+                "average_score": {"$avg": "$ratings"},
+                "scores_counted": {"$size": "$ratings"},
+                "last_modified": 1,
+                "has_scores": {"$gt": [{"$size": "$ratings"}, 0]},
+            }},
+            doc! {"$sort": sort_doc},
+        ];
+
+        if let Some(limit) = filter.limit {
+            pipeline.push(doc! {"$limit": i64::from(limit)});
+        }
+
+        pipeline.push(doc! {"$unset": "has_scores"});
+        let mut results = vec![];
+        let mut session = self.start_session(causal).await?;
+        let aggregate_options = AggregateOptions::builder()
+            .read_concern(self.settings.listing_read_concern.clone())
+            .build();
+        let mut cursor =
+            self.coll.aggregate_with_session(pipeline, aggregate_options, &mut session).await?;
+
+        while let Some(doc) = cursor.next(&mut session).await {
+            results.push(bson::from_document::<BookScoresSummary>(doc?)?);
+        }
+
+        causal.observe(&session);
+        Ok(results)
+    }
+
+    // Insert new book score, only matching the book if the reviewer has no existing score for it
+    // so that the check & the push are atomic
     //
-    pub async fn db_insert_book_score(&self, book: &Book) -> Result<(), Box<dyn Error>> {
-        let title = get_or_err(book.title.as_ref(), "title")?;
-        let author = get_or_err(book.author.as_ref(), "author")?;
-        let scores = get_or_err(book.scores.as_ref(), "scores")?;
-        let score = get_or_err(scores.first(), "scores[0]")?;
-        let reference = get_or_err(score.reference.as_ref(), "scores[0].reference")?;
-        let rating = get_or_err(score.rating.as_ref(), "scores[0].rating")?;
-        self.coll
-            .update_one(
-                doc! {"title": title, "author": author},
+    async fn db_insert_book_score(
+        &self, key: &BookKey, book: &Book, causal: &CausalContext,
+    ) -> Result<WriteCounts, AppError> {
+        let (reference, rating) = get_new_score_fields(book)?;
+        let mut session = self.start_session(causal).await?;
+        let mut filter_doc = key.filter();
+        filter_doc.insert("scores.reference", doc! {"$ne": reference});
+        let result = self
+            .coll
+            .update_one_with_session(
+                filter_doc,
                 doc! {
                     "$push": {"scores": {"reference": reference, "rating": rating}},
-                    "$set": {"last_modified": DateTime::now()}
+                    "$set": {"last_modified": DateTime::now()},
+                    "$inc": {"version": 1}
                 },
                 None,
+                &mut session,
             )
             .await?;
-        Ok(())
+
+        let has_dup_score =
+            result.matched_count == 0 && self.book_exists(key, &mut session).await?;
+        causal.observe(&session);
+
+        if has_dup_score {
+            return Err(dup_score_error(reference));
+        }
+
+        Ok(result.into())
     }
 
-    // Update existing book record adding new quantity
+    // Replace book score using a single pipeline update, which filters out all the reviewer's
+    // existing scores and appends the new one, so concurrent readers & writers never see the
+    // reviewer without a score or with more than one score (requires MongoDB 4.2+)
     //
-    pub async fn db_update_book_score(&self, book: &Book) -> Result<(), Box<dyn Error>> {
-        self.db_delete_book_scores(book).await?;
-        self.db_insert_book_score(book).await?;
-        Ok(())
+    async fn db_update_book_score(
+        &self, key: &BookKey, book: &Book, causal: &CausalContext,
+    ) -> Result<WriteCounts, AppError> {
+        let (reference, rating) = get_new_score_fields(book)?;
+        let mut session = self.start_session(causal).await?;
+        let result = self
+            .coll
+            .update_one_with_session(
+                key.filter(),
+                replace_score_pipeline(reference, rating, DateTime::now()),
+                None,
+                &mut session,
+            )
+            .await?;
+        causal.observe(&session);
+        Ok(result.into())
+    }
+
+    // Change the rating of every score in a book's record from the matching reviewer reference
+    //
+    async fn db_change_book_score(
+        &self, key: &BookKey, book: &Book, causal: &CausalContext,
+    ) -> Result<WriteCounts, AppError> {
+        let (reference, rating) = get_new_score_fields(book)?;
+        let mut session = self.start_session(causal).await?;
+        let mut filter_doc = key.filter();
+        filter_doc.insert("scores.reference", reference);
+        let update_options = UpdateOptions::builder()
+            .array_filters(vec![doc! {"score.reference": reference}])
+            .build();
+        let result = self
+            .coll
+            .update_one_with_session(
+                filter_doc,
+                doc! {
+                    "$set": {"scores.$[score].rating": rating, "last_modified": DateTime::now()},
+                    "$inc": {"version": 1}
+                },
+                update_options,
+                &mut session,
+            )
+            .await?;
+        causal.observe(&session);
+        Ok(result.into())
     }
 
     // Delete a score from a book's record for the matching reviewer reference
     //
-    pub async fn db_delete_book_scores(&self, book: &Book) -> Result<(), Box<dyn Error>> {
-        let title = get_or_err(book.title.as_ref(), "title")?;
-        let author = get_or_err(book.author.as_ref(), "author")?;
-        let scores = get_or_err(book.scores.as_ref(), "scores")?;
-        let score = get_or_err(scores.first(), "scores[0]")?;
-        if let Some(reference) = &score.reference {
-            self.coll
-                .update_one(
-                    doc! {"title": title, "author": author},
-                    doc! {
-                        "$pull": {"scores": {"reference": reference}},
-                        "$set": {"last_modified": DateTime::now()}
-                    },
-                    None,
-                )
-                .await?;
+    async fn db_delete_book_scores(
+        &self, key: &BookKey, book: &Book, causal: &CausalContext,
+    ) -> Result<WriteCounts, AppError> {
+        let reference = get_score_ref_fields(book)?;
+        let mut session = self.start_session(causal).await?;
+        let mut filter_doc = key.filter();
+        filter_doc.insert("scores.reference", reference);
+        let result = self
+            .coll
+            .update_one_with_session(
+                filter_doc,
+                doc! {
+                    "$pull": {"scores": {"reference": reference}},
+                    "$set": {"last_modified": DateTime::now()},
+                    "$inc": {"version": 1}
+                },
+                None,
+                &mut session,
+            )
+            .await?;
+        causal.observe(&session);
+        Ok(result.into())
+    }
+}
+
+// Build the update pipeline which replaces every score from the reviewer reference in a book's
+// scores array with the new rating, in a single atomic update, where the reference is a literal so
+// a reference starting with '$' can't be evaluated as a field path
+//
+fn replace_score_pipeline(reference: &str, rating: f64, now: DateTime) -> Vec<Document> {
+    vec![doc! {
+        "$set": {
+            "scores": {
+                "$concatArrays": [
+                    {"$filter": {
+                        "input": {"$ifNull": ["$scores", []]},
+                        "cond": {"$ne": ["$$this.reference", {"$literal": reference}]},
+                    }},
+                    [{"reference": {"$literal": reference}, "rating": rating}],
+                ]
+            },
+            "last_modified": now,
+            "version": {"$add": [{"$ifNull": ["$version", 0]}, 1]},
         }
+    }]
+}
+
+// Extract the fields required to add a new score to a book, returning an error naming the field of
+// the client's payload (`reference` or `score`) if any are missing
+//
+pub fn get_new_score_fields(book: &Book) -> Result<(&String, f64), AppError> {
+    let reference = get_score_ref_fields(book)?;
+    let score = book.scores.as_ref().and_then(|scores| scores.first());
+    let rating = get_or_err(score.and_then(|score| score.rating.as_ref()), "score")?;
+    Ok((reference, *rating))
+}
+
+// Extract the reviewer reference identifying a reviewer's score for a book, returning an error
+// naming the field of the client's payload (`reference`) if it's missing
+//
+pub fn get_score_ref_fields(book: &Book) -> Result<&String, AppError> {
+    let score = book.scores.as_ref().and_then(|scores| scores.first());
+    let reference = get_or_err(score.and_then(|score| score.reference.as_ref()), "reference")?;
+    Ok(reference)
+}
+
+// Read a book's scores array tolerating malformed content, as other apps sharing the books
+// collection may write score sub-documents which don't match app2's expectations, where an array
+// element which isn't a document is read as a score with no fields, and a non-array is ignored
+//
+fn deserialize_lenient_scores<'de, D>(deserializer: D) -> Result<Option<Vec<Score>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let scores = match Option::<Bson>::deserialize(deserializer)? {
+        Some(Bson::Array(array)) => Some(
+            array
+                .into_iter()
+                .map(|elem| match elem {
+                    Bson::Document(doc) => bson::from_document(doc).unwrap_or_default(),
+                    _ => Score::default(),
+                })
+                .collect(),
+        ),
+        _ => None,
+    };
+    Ok(scores)
+}
+
+// Read a score's reviewer reference, treating a value which isn't a string as missing
+//
+fn deserialize_lenient_string<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<Bson>::deserialize(deserializer)? {
+        Some(Bson::String(val)) => Ok(Some(val)),
+        _ => Ok(None),
+    }
+}
 
-        Ok(())
+// Read a score's rating from any numeric type, treating a non-numeric or non-finite value as
+// missing
+//
+fn deserialize_lenient_rating<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let rating = match Option::<Bson>::deserialize(deserializer)? {
+        Some(Bson::Int32(val)) => Some(f64::from(val)),
+        Some(Bson::Int64(val)) => Some(val as f64),
+        Some(Bson::Double(val)) if val.is_finite() => Some(val),
+        _ => None,
+    };
+    Ok(rating)
+}
+
+// Build error for when a reviewer reference already has a score recorded for a book
+//
+pub fn dup_score_error(reference: &str) -> AppError {
+    AppError::DuplicateKey {
+        field: Some(String::from("reference")),
+        message: format!("A review score from reference `{}` already exists for book", reference),
     }
 }
 
 // Validate specific variable field has a value, returning it, otherwise returning an error
 //
-fn get_or_err<'a, T>(field: Option<&'a T>, fieldname: &str) -> Result<&'a T, Box<dyn Error>> {
-    field.ok_or_else(|| format!("Field `{}` is empty, but is required", fieldname).into())
+pub fn get_or_err<'a, T>(field: Option<&'a T>, fieldname: &str) -> Result<&'a T, AppError> {
+    field.ok_or_else(|| AppError::MissingField(fieldname.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn score_replaced_in_one_pipeline_stage() {
+        let now = DateTime::from_millis(1_640_995_199_000);
+        let pipeline = replace_score_pipeline("Reviewer A", 7.5, now);
+        assert_eq!(
+            pipeline,
+            [doc! {
+                "$set": {
+                    "scores": {
+                        "$concatArrays": [
+                            {"$filter": {
+                                "input": {"$ifNull": ["$scores", []]},
+                                "cond": {"$ne": ["$$this.reference", {"$literal": "Reviewer A"}]},
+                            }},
+                            [{"reference": {"$literal": "Reviewer A"}, "rating": 7.5}],
+                        ]
+                    },
+                    "last_modified": now,
+                    "version": {"$add": [{"$ifNull": ["$version", 0]}, 1]},
+                }
+            }]
+        );
+    }
+
+    #[test]
+    fn score_reference_never_evaluated() {
+        let pipeline = replace_score_pipeline("$title", 1.0, DateTime::now());
+        let set_doc = pipeline[0].get_document("$set").unwrap();
+        let concat = set_doc.get_document("scores").unwrap().get_array("$concatArrays").unwrap();
+        let new_scores = concat[1].as_array().unwrap();
+        assert_eq!(
+            new_scores[0],
+            Bson::from(doc! {
+                "reference": {"$literal": "$title"}, "rating": 1.0
+            })
+        );
+        let filter = concat[0].as_document().unwrap().get_document("$filter").unwrap();
+        assert_eq!(
+            filter.get_document("cond").unwrap(),
+            &doc! {
+                "$ne": ["$$this.reference", {"$literal": "$title"}]
+            }
+        );
+    }
 }
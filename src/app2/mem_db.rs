@@ -0,0 +1,264 @@
+use async_trait::async_trait;
+use bson::{doc, Bson, DateTime};
+use std::cmp::Ordering;
+
+use super::db::{
+    dup_score_error, get_new_score_fields, get_score_ref_fields, Book, BookScoresFilter,
+    BookScoresStore, BookScoresSummary,
+};
+use super::scoring::score_stats;
+use crate::causal::CausalContext;
+use crate::error::AppError;
+use crate::fields::FieldSelection;
+use crate::mem_store::{
+    array_contains, field_equals, inc_field, pull_from_array, push_to_array, MemCollection,
+};
+use crate::store::{BookKey, WriteCounts};
+
+// In-memory book scores manager, for running app2 without a MongoDB database
+#[derive(Debug, Clone, Default)]
+pub struct MemBookScoresMgr {
+    coll: MemCollection,
+}
+
+impl MemBookScoresMgr {
+    // Create new instance of in-memory book scores manager over the provided in-memory collection
+    //
+    pub fn new(coll: MemCollection) -> Self {
+        Self { coll }
+    }
+}
+
+// Manages interaction with in-memory books collection, emulating the MongoDB book scores manager,
+// where every operation trivially observes all earlier operations, so the causal context is unused
+//
+#[async_trait]
+impl BookScoresStore for MemBookScoresMgr {
+    // Query books returning list of book scores for a book, where the whole of the book is returned
+    // regardless of the field selection as the records are already in memory
+    //
+    async fn db_find_book_scores(
+        &self, key: &BookKey, _fields: &FieldSelection, _causal: &CausalContext,
+    ) -> Result<Option<Book>, AppError> {
+        let doc = self.coll.find_one(|doc| key.matches(doc));
+        Ok(doc.map(bson::from_document).transpose()?)
+    }
+
+    // Summarize the scores of matching books, emulating the MongoDB manager's aggregation pipeline
+    //
+    async fn db_summarize_book_scores(
+        &self, filter: &BookScoresFilter, _causal: &CausalContext,
+    ) -> Result<Vec<BookScoresSummary>, AppError> {
+        let docs = self.coll.find(|doc| {
+            filter.author.as_ref().is_none_or(|author| field_equals(doc, "author", author))
+        });
+        let mut results = vec![];
+
+        for doc in docs {
+            let book: Book = bson::from_document(doc)?;
+
+            if filter.year.is_some() && book.year != filter.year {
+                continue;
+            }
+
+            let stats = score_stats(book.scores.as_deref().unwrap_or_default());
+            results.push(BookScoresSummary {
+                id: book.id,
+                title: book.title,
+                author: book.author,
+                year: book.year,
+                average_score: stats.mean,
+                scores_counted: stats.counted as u32,
+                last_modified: book.last_modified,
+            });
+        }
+
+        results.sort_by(|book1, book2| {
+            let by_average = match filter.sort {
+                Some(sort) => sort.compare(book1.average_score, book2.average_score),
+                None => Ordering::Equal,
+            };
+            by_average
+                .then_with(|| (&book1.title, &book1.author).cmp(&(&book2.title, &book2.author)))
+        });
+
+        if let Some(limit) = filter.limit {
+            results.truncate(limit as usize);
+        }
+
+        Ok(results)
+    }
+
+    // Insert new book score, unless the reviewer already has a score for the book
+    //
+    async fn db_insert_book_score(
+        &self, key: &BookKey, book: &Book, _causal: &CausalContext,
+    ) -> Result<WriteCounts, AppError> {
+        let (reference, rating) = get_new_score_fields(book)?;
+        self.coll.update_one(
+            |doc| key.matches(doc),
+            |doc| {
+                if array_contains(doc, "scores", |elem| is_score_from(elem, reference)) {
+                    return Err(dup_score_error(reference));
+                }
+
+                push_to_array(
+                    doc,
+                    "scores",
+                    doc! {"reference": reference, "rating": rating}.into(),
+                )?;
+                doc.insert("last_modified", DateTime::now());
+                inc_field(doc, "version", 1)?;
+                Ok(true)
+            },
+        )
+    }
+
+    // Replace book score in a single locked update, removing all the reviewer's existing scores and
+    // appending the new one
+    //
+    async fn db_update_book_score(
+        &self, key: &BookKey, book: &Book, _causal: &CausalContext,
+    ) -> Result<WriteCounts, AppError> {
+        let (reference, rating) = get_new_score_fields(book)?;
+        self.coll.update_one(
+            |doc| key.matches(doc),
+            |doc| {
+                pull_from_array(doc, "scores", |elem| is_score_from(elem, reference));
+                push_to_array(
+                    doc,
+                    "scores",
+                    doc! {"reference": reference, "rating": rating}.into(),
+                )?;
+                doc.insert("last_modified", DateTime::now());
+                inc_field(doc, "version", 1)?;
+                Ok(true)
+            },
+        )
+    }
+
+    // Change the rating of every score in a book's record from the matching reviewer reference
+    //
+    async fn db_change_book_score(
+        &self, key: &BookKey, book: &Book, _causal: &CausalContext,
+    ) -> Result<WriteCounts, AppError> {
+        let (reference, rating) = get_new_score_fields(book)?;
+        let is_reviewers_score = |elem: &Bson| is_score_from(elem, reference);
+        self.coll.update_one(
+            |doc| key.matches(doc) && array_contains(doc, "scores", is_reviewers_score),
+            |doc| {
+                if let Ok(scores) = doc.get_array_mut("scores") {
+                    for score in scores.iter_mut().filter(|elem| is_reviewers_score(elem)) {
+                        if let Bson::Document(score) = score {
+                            score.insert("rating", rating);
+                        }
+                    }
+                }
+
+                doc.insert("last_modified", DateTime::now());
+                inc_field(doc, "version", 1)?;
+                Ok(true)
+            },
+        )
+    }
+
+    // Delete a score from a book's record for the matching reviewer reference
+    //
+    async fn db_delete_book_scores(
+        &self, key: &BookKey, book: &Book, _causal: &CausalContext,
+    ) -> Result<WriteCounts, AppError> {
+        let reference = get_score_ref_fields(book)?;
+        let is_reviewers_score = |elem: &Bson| is_score_from(elem, reference);
+        self.coll.update_one(
+            |doc| key.matches(doc) && array_contains(doc, "scores", is_reviewers_score),
+            |doc| {
+                pull_from_array(doc, "scores", is_reviewers_score);
+                doc.insert("last_modified", DateTime::now());
+                inc_field(doc, "version", 1)?;
+                Ok(true)
+            },
+        )
+    }
+}
+
+// Check whether an element of a book's scores array was recorded by the given reviewer reference
+//
+fn is_score_from(elem: &Bson, reference: &str) -> bool {
+    match elem {
+        Bson::Document(score) => field_equals(score, "reference", reference),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app2::db::Score;
+    use std::sync::Arc;
+
+    const REFERENCES: &[&str] = &["Reviewer A", "Reviewer B", "Reviewer C"];
+    const UPDATES_PER_REFERENCE: usize = 20;
+
+    // Book payload identifying the book by its key, with a score from the reviewer reference
+    //
+    fn score_book(reference: &str, rating: f64) -> Book {
+        Book {
+            id: None,
+            title: Some(String::from("Test Book")),
+            author: Some(String::from("Test Writer")),
+            year: None,
+            scores: Some(vec![Score {
+                reference: Some(reference.to_string()),
+                rating: Some(rating),
+            }]),
+            last_modified: None,
+            version: None,
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_score_updates_leave_one_score_per_reference() {
+        let coll = MemCollection::default();
+        coll.insert_one(doc! {"title": "Test Book", "author": "Test Writer"}).unwrap();
+        let mgr = Arc::new(MemBookScoresMgr::new(coll.clone()));
+        let key = BookKey::from_title_author(
+            Some(&String::from("Test Book")),
+            Some(&String::from("Test Writer")),
+        )
+        .unwrap();
+        mgr.db_insert_book_score(&key, &score_book(REFERENCES[0], 1.0), &CausalContext::default())
+            .await
+            .unwrap();
+        let mut tasks = vec![];
+
+        for update in 0..UPDATES_PER_REFERENCE {
+            for reference in REFERENCES {
+                let (mgr, key) = (mgr.clone(), key.clone());
+                let book = score_book(reference, update as f64);
+                tasks.push(tokio::spawn(async move {
+                    mgr.db_update_book_score(&key, &book, &CausalContext::default()).await
+                }));
+            }
+        }
+
+        for task in tasks {
+            assert!(!task.await.unwrap().unwrap().is_unmatched());
+        }
+
+        let book: Book =
+            bson::from_document(coll.find_one(|doc| key.matches(doc)).unwrap()).unwrap();
+        let scores = book.scores.unwrap();
+        assert_eq!(scores.len(), REFERENCES.len());
+
+        for reference in REFERENCES {
+            let matching: Vec<&Score> = scores
+                .iter()
+                .filter(|score| score.reference.as_deref() == Some(*reference))
+                .collect();
+            assert_eq!(matching.len(), 1);
+            assert!(matching[0].rating.unwrap() < UPDATES_PER_REFERENCE as f64);
+        }
+
+        assert_eq!(book.version, Some((1 + REFERENCES.len() * UPDATES_PER_REFERENCE) as i64));
+    }
+}
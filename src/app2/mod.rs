@@ -1,144 +1,734 @@
 use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
 use std::error::Error;
-use std::net::Ipv4Addr;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
 use warp::{http, Filter};
 
+use crate::causal::{capture_causal_context, causal_reply, CausalContext};
+use crate::conditional::{
+    capture_if_match_header, capture_read_conditions, if_match_versions, versioned_reply,
+    ReadConditions, Version,
+};
+use crate::config::App2Config;
+use crate::error::{handle_rejection, AppError};
+use crate::fields::FieldSelection;
+use crate::store::{BookKey, StoreConnection, StoreLocation};
+
 mod db;
-use db::{Book, BookScoresMgr, Score};
+use db::{
+    Book, BookScoresFilter, BookScoresMgr, BookScoresStore, BookScoresSummary, Score, OWNED_FIELDS,
+};
+
+mod mem_db;
+use mem_db::MemBookScoresMgr;
+
+mod scoring;
+use scoring::{score_stats, ScoreScale, ScoreSort};
 
-const LISTEN_ADDRESS: Ipv4Addr = Ipv4Addr::new(127, 0, 0, 1);
-const LISTEN_PORT: u16 = 8282;
 const RSC_VERSION: &str = "v1";
+const RSC_VERSION_V2: &str = "v2";
 const RSC_NAME: &str = "books";
-const PAYLOAD_LIMIT: u64 = 1024 * 16;
+const SCORES_RSC_NAME: &str = "scores";
 
-// Book record to extract from/to JSON payload
+// Book record to extract from JSON payload or query string, where the reference is the reviewer
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct BookPayload {
     pub title: Option<String>,
     pub author: Option<String>,
     pub year: Option<i32>,
     pub reference: Option<String>,
-    pub score: Option<f32>,
+    pub score: Option<f64>,
+}
+
+// Query string parameters for reading a book's scores, or if no title is provided, for browsing the
+// scores of all books, optionally filtered by author or year, sorted by average score and limited,
+// where either can be narrowed to a list of fields (eg. 'title,scores')
+#[derive(Debug, Deserialize, Clone)]
+pub struct BooksQuery {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub year: Option<i32>,
+    pub sort: Option<String>,
+    pub limit: Option<u32>,
+    pub fields: Option<String>,
+}
+
+// Query string parameters for reading a single book's scores, optionally narrowed to a list of
+// fields
+#[derive(Debug, Deserialize, Clone)]
+pub struct BookQuery {
+    pub fields: Option<String>,
+}
+
+// Query string parameters identifying the reviewer reference whose score to delete from a book
+#[derive(Debug, Deserialize, Clone)]
+pub struct ScoreQuery {
+    pub reference: Option<String>,
+}
+
+// Query string parameters for listing a book's individual review scores, optionally only for one
+// reviewer reference and sorted by rating
+#[derive(Debug, Deserialize, Clone)]
+pub struct ScoresQuery {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub reference: Option<String>,
+    pub sort: Option<String>,
+}
+
+// Book scores summary JSON payload returned by API v1 reads, which for backwards compatibility
+// overloads the 'reference' field with a note and the 'score' field with the average score, and
+// where the time of last modification is an RFC 3339 timestamp
+#[derive(Debug, Serialize, Clone)]
+pub struct BookScoresV1Payload {
+    pub id: Option<String>,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub year: Option<i32>,
+    pub reference: Option<String>,
+    pub score: Option<f64>,
+    pub median_score: Option<f64>,
+    pub min_score: Option<f64>,
+    pub max_score: Option<f64>,
+    pub scores_counted: Option<usize>,
+    pub scores_ignored: Option<usize>,
+    pub last_modified: Option<String>,
 }
 
-// App2 main function to setup book scores REST API service
+// Book scores JSON payload returned by API v2 reads, where the time of last modification is an
+// RFC 3339 timestamp
+#[derive(Debug, Serialize, Clone)]
+pub struct BookScoresPayload {
+    pub id: Option<String>,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub year: Option<i32>,
+    pub average_score: Option<f64>,
+    pub median_score: Option<f64>,
+    pub min_score: Option<f64>,
+    pub max_score: Option<f64>,
+    pub scores_counted: usize,
+    pub scores_ignored: usize,
+    pub note: String,
+    pub scores: Vec<ScorePayload>,
+    pub last_modified: Option<String>,
+}
+
+// Book scores summary JSON payload returned by both API versions when browsing all books, where
+// the time of last modification is an RFC 3339 timestamp
+#[derive(Debug, Serialize, Clone)]
+pub struct BookScoresSummaryPayload {
+    pub id: Option<String>,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub year: Option<i32>,
+    pub average_score: Option<f64>,
+    pub scores_counted: u32,
+    pub last_modified: Option<String>,
+}
+
+// Individual review score JSON payload returned by API v2 reads
+#[derive(Debug, Serialize, Clone)]
+pub struct ScorePayload {
+    pub reference: Option<String>,
+    pub rating: Option<f64>,
+}
+
+// How to handle a POST of a new score from a reviewer reference which already has a score for
+// the book, configured as 'reject' or 'upsert'
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DupScorePolicy {
+    // Reject the new score with a '409 Conflict' response
+    Reject,
+    // Replace the reviewer's existing score with the new score
+    Upsert,
+}
+
+// App2 main function to setup book scores REST API service, over the connection to the store at
+// the location, until the shutdown future completes, when requests in progress are finished first
 //
-pub async fn app2_main(url: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
-    println!("App2 running against MongoDB database at '{}'", url);
-    let book_scores_mgr = BookScoresMgr::new(url).await?;
-    let book_scores_mgr_ref = warp::any().map(move || book_scores_mgr.clone());
-    let api_path_filter_chain =
-        warp::path(RSC_VERSION).and(warp::path(RSC_NAME)).and(warp::path::end());
-    let api_path_json_capture_filter_chain =
-        api_path_filter_chain.and(capture_book_body_json()).and(book_scores_mgr_ref.clone());
-    // CREATE: HTTP POST filter chain
-    let add_items =
-        warp::post().and(api_path_json_capture_filter_chain.clone()).and_then(insert_book_score);
-    // READ: HTTP GET filter chain
-    let get_items = warp::get()
-        .and(api_path_filter_chain)
-        .and(capture_book_query_string())
-        .and(book_scores_mgr_ref.clone())
-        .and_then(get_book_score);
-    // UPDATE: HTTP PUT filter chain
-    let update_item =
-        warp::put().and(api_path_json_capture_filter_chain.clone()).and_then(update_book_score);
-    // DELETE: HTTP DELETE filter chain
-    let delete_item =
-        warp::delete().and(api_path_json_capture_filter_chain.clone()).and_then(delete_book_score);
-    let routes = add_items.or(get_items).or(update_item).or(delete_item);
+pub async fn app2_main(
+    location: &StoreLocation, connection: &StoreConnection, listen: SocketAddr,
+    config: &App2Config, shutdown: impl Future<Output = ()> + Send + 'static,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    println!("App2 running against {}", location);
+    let book_scores_mgr: Arc<dyn BookScoresStore> = match connection {
+        StoreConnection::InMemory(coll) => Arc::new(MemBookScoresMgr::new(coll.clone())),
+        StoreConnection::MongoDb { client, db_name, coll_name } => {
+            Arc::new(BookScoresMgr::new(client, db_name, coll_name, &config.operation_settings()?))
+        }
+    };
+    let dup_score_policy = config.duplicate_score_policy;
+    println!("- Duplicate review score policy: {:?}", dup_score_policy);
+    let score_scale =
+        ScoreScale { min: config.score_min, max: config.score_max, step: config.score_step };
+    println!("- Review scores {}", score_scale);
+    let routes =
+        book_scores_routes(book_scores_mgr, config.payload_limit, dup_score_policy, score_scale);
+    let (_, server) = warp::serve(routes)
+        .try_bind_with_graceful_shutdown(listen, shutdown)
+        .map_err(|e| format!("Can't listen on {}: {}", listen, e))?;
+    println!("- HTTP REST API listening on: http://{}/{}/{}", listen, RSC_VERSION, RSC_NAME);
     println!(
-        "- HTTP REST API listening on: http://{}:{}/{}/{}",
-        LISTEN_ADDRESS, LISTEN_PORT, RSC_VERSION, RSC_NAME
+        "- Eg1: http://{}/{}/{}?title=The%20Last%20Man&author=Mary%20Shelley",
+        listen, RSC_VERSION, RSC_NAME
     );
     println!(
-        "- Eg1: http://{}:{}/{}/{}?title=The%20Last%20Man&author=Mary%20Shelley",
-        LISTEN_ADDRESS, LISTEN_PORT, RSC_VERSION, RSC_NAME
+        "- Eg2: http://{}/{}/{}?title=The%20Day%20of%20the%20Triffids&\
+             author=John%20Wyndham",
+        listen, RSC_VERSION, RSC_NAME
     );
     println!(
-        "- Eg2: http://{}:{}/{}/{}?title=The%20Day%20of%20the%20Triffids&\
-             author=John%20Wyndham",
-        LISTEN_ADDRESS, LISTEN_PORT, RSC_VERSION, RSC_NAME
+        "- Eg3: http://{}/{}/{}?title=The%20Last%20Man&author=Mary%20Shelley",
+        listen, RSC_VERSION_V2, RSC_NAME
+    );
+    println!("- Eg4: http://{}/{}/{}?sort=-rating&limit=10", listen, RSC_VERSION_V2, RSC_NAME);
+    println!(
+        "- Eg5: http://{}/{}/{}/{}?title=The%20Last%20Man&author=Mary%20Shelley&sort=-rating",
+        listen, RSC_VERSION, RSC_NAME, SCORES_RSC_NAME
+    );
+    println!(
+        "- Eg6: http://{}/{}/{}?author=John%20Wyndham&fields=title,scores",
+        listen, RSC_VERSION_V2, RSC_NAME
     );
-    warp::serve(routes).run((LISTEN_ADDRESS, LISTEN_PORT)).await;
+    println!("- Eg7: http://{}/{}/{}/{{id}}", listen, RSC_VERSION_V2, RSC_NAME);
+    server.await;
     Ok(())
 }
 
+// Filter chains of the book scores REST API, routing each request to its handler, where any
+// rejection becomes an error response
+//
+fn book_scores_routes(
+    book_scores_mgr: Arc<dyn BookScoresStore>, payload_limit: u64,
+    dup_score_policy: DupScorePolicy, score_scale: ScoreScale,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = Infallible> + Clone {
+    let score_scale_ref = warp::any().map(move || score_scale);
+    let book_scores_mgr_ref = warp::any().map(move || book_scores_mgr.clone());
+    // Each request's operations run in the causal context of the client's earlier requests
+    let causal_book_scores_mgr_ref = capture_causal_context().and(book_scores_mgr_ref);
+    let api_v1_path_filter_chain =
+        warp::path(RSC_VERSION).and(warp::path(RSC_NAME)).and(warp::path::end());
+    let api_v2_path_filter_chain =
+        warp::path(RSC_VERSION_V2).and(warp::path(RSC_NAME)).and(warp::path::end());
+    // Writes accept the same payload in both API versions
+    let api_path_filter_chain = api_v1_path_filter_chain.or(api_v2_path_filter_chain).unify();
+    let api_path_json_capture_filter_chain = api_path_filter_chain
+        .and(capture_book_body_json(payload_limit))
+        .and(causal_book_scores_mgr_ref.clone());
+    let api_path_json_conditional_filter_chain = api_path_filter_chain
+        .and(capture_book_body_json(payload_limit))
+        .and(capture_if_match_header())
+        .and(causal_book_scores_mgr_ref.clone());
+    let api_v1_item_path_filter_chain = warp::path(RSC_VERSION)
+        .and(warp::path(RSC_NAME))
+        .and(warp::path::param::<String>())
+        .and(warp::path::end());
+    let api_v2_item_path_filter_chain = warp::path(RSC_VERSION_V2)
+        .and(warp::path(RSC_NAME))
+        .and(warp::path::param::<String>())
+        .and(warp::path::end());
+    let api_item_path_filter_chain =
+        api_v1_item_path_filter_chain.or(api_v2_item_path_filter_chain).unify();
+    let api_item_path_json_conditional_filter_chain = api_item_path_filter_chain
+        .and(capture_book_body_json(payload_limit))
+        .and(capture_if_match_header())
+        .and(causal_book_scores_mgr_ref.clone());
+    // CREATE: HTTP POST filter chain
+    let add_items = warp::post()
+        .and(api_path_json_capture_filter_chain.clone())
+        .and(warp::any().map(move || dup_score_policy))
+        .and(score_scale_ref)
+        .and_then(insert_book_score);
+    // READ: HTTP GET filter chain
+    let get_items = warp::get()
+        .and(api_v1_path_filter_chain)
+        .and(capture_book_query_string())
+        .and(capture_read_conditions())
+        .and(causal_book_scores_mgr_ref.clone())
+        .and_then(get_book_score);
+    let get_items_v2 = warp::get()
+        .and(api_v2_path_filter_chain)
+        .and(capture_book_query_string())
+        .and(capture_read_conditions())
+        .and(causal_book_scores_mgr_ref.clone())
+        .and_then(get_book_scores_v2);
+    let get_scores = warp::get()
+        .and(warp::path(RSC_VERSION).or(warp::path(RSC_VERSION_V2)).unify())
+        .and(warp::path(RSC_NAME))
+        .and(warp::path(SCORES_RSC_NAME))
+        .and(warp::path::end())
+        .and(warp::query::query())
+        .and(causal_book_scores_mgr_ref.clone())
+        .and_then(get_book_scores_list);
+    let get_item = warp::get()
+        .and(api_v1_item_path_filter_chain)
+        .and(warp::query::query())
+        .and(capture_read_conditions())
+        .and(causal_book_scores_mgr_ref.clone())
+        .and_then(get_book_score_item);
+    let get_item_v2 = warp::get()
+        .and(api_v2_item_path_filter_chain)
+        .and(warp::query::query())
+        .and(capture_read_conditions())
+        .and(causal_book_scores_mgr_ref.clone())
+        .and_then(get_book_scores_v2_item);
+    // UPDATE: HTTP PUT & PATCH filter chains
+    let update_items = warp::put()
+        .and(api_path_json_conditional_filter_chain.clone())
+        .and(score_scale_ref)
+        .and_then(update_book_score);
+    let update_item = warp::put()
+        .and(api_item_path_json_conditional_filter_chain.clone())
+        .and(score_scale_ref)
+        .and_then(update_book_score_item);
+    let patch_item = warp::patch()
+        .and(api_item_path_json_conditional_filter_chain.clone())
+        .and(score_scale_ref)
+        .and_then(patch_book_score_item);
+    // DELETE: HTTP DELETE filter chains
+    let delete_items = warp::delete()
+        .and(api_path_json_conditional_filter_chain.clone())
+        .and_then(delete_book_score);
+    let delete_item = warp::delete()
+        .and(api_item_path_filter_chain)
+        .and(warp::query::query())
+        .and(capture_if_match_header())
+        .and(causal_book_scores_mgr_ref.clone())
+        .and_then(delete_book_score_item);
+    add_items
+        .or(get_items)
+        .or(get_items_v2)
+        .or(get_scores)
+        .or(get_item)
+        .or(get_item_v2)
+        .or(update_items)
+        .or(update_item)
+        .or(patch_item)
+        .or(delete_items)
+        .or(delete_item)
+        .recover(handle_rejection)
+}
+
 // Capture book http query string parameters
 //
 fn capture_book_query_string(
-) -> impl Filter<Extract = (BookPayload,), Error = warp::Rejection> + Clone {
+) -> impl Filter<Extract = (BooksQuery,), Error = warp::Rejection> + Clone {
     warp::query::query()
 }
 
-// Capture book http request payload JSON content
+// Capture book http request payload JSON content, rejecting payloads larger than the limit
 //
 fn capture_book_body_json(
+    payload_limit: u64,
 ) -> impl Filter<Extract = (BookPayload,), Error = warp::Rejection> + Clone {
-    warp::body::content_length_limit(PAYLOAD_LIMIT).and(warp::body::json())
+    warp::body::content_length_limit(payload_limit).and(warp::body::json())
 }
 
-// Insert book score sub-record in back-end DB
+// Insert book score sub-record in back-end DB, applying the duplicate score policy if the reviewer
+// has already scored the book
 //
 async fn insert_book_score(
-    book_payload: BookPayload, book_scores_mgr: BookScoresMgr,
+    book_payload: BookPayload, causal: CausalContext, book_scores_mgr: Arc<dyn BookScoresStore>,
+    dup_score_policy: DupScorePolicy, score_scale: ScoreScale,
 ) -> Result<impl warp::Reply, warp::Rejection> {
-    match book_scores_mgr.db_insert_book_score(&book_payload_to_book(&book_payload)).await {
-        Ok(_) => Ok(warp::reply::with_status(
-            "Added new review score for book",
-            http::StatusCode::CREATED,
+    validate_score(&book_payload, &score_scale)?;
+    let key = book_payload_to_key(&book_payload).map_err(warp::reject::custom)?;
+    let book = book_payload_to_book(&book_payload);
+    let result = match dup_score_policy {
+        DupScorePolicy::Reject => book_scores_mgr.db_insert_book_score(&key, &book, &causal).await,
+        DupScorePolicy::Upsert => book_scores_mgr.db_update_book_score(&key, &book, &causal).await,
+    };
+
+    match result {
+        Ok(counts) if counts.is_unmatched() => Err(book_not_found(&key)),
+        Ok(_) => Ok(causal_reply(
+            warp::reply::with_status("Added new review score for book", http::StatusCode::CREATED),
+            &causal,
         )),
         Err(e) => {
-            eprintln!("Error inserting data: {:#?}", e);
-            Err(warp::reject())
+            eprintln!("Error inserting data: {}", e);
+            Err(warp::reject::custom(e))
         }
     }
 }
 
-// Update book score sub-record in back-end DB
+// Update book score sub-record in back-end DB, identified by the payload's title & author, if the
+// book is still at a version the request requires
 //
 async fn update_book_score(
-    book_payload: BookPayload, book_scores_mgr: BookScoresMgr,
+    book_payload: BookPayload, if_match: Option<String>, causal: CausalContext,
+    book_scores_mgr: Arc<dyn BookScoresStore>, score_scale: ScoreScale,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    validate_score(&book_payload, &score_scale)?;
+    let key = book_payload_to_key(&book_payload).map_err(warp::reject::custom)?;
+    let key = key.at_versions(if_match_versions(if_match.as_deref()));
+    set_book_score(&key, &book_payload, &causal, book_scores_mgr).await
+}
+
+// Update book score sub-record in back-end DB, for the book with the id, if the book is still at a
+// version the request requires
+//
+async fn update_book_score_item(
+    id: String, book_payload: BookPayload, if_match: Option<String>, causal: CausalContext,
+    book_scores_mgr: Arc<dyn BookScoresStore>, score_scale: ScoreScale,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    validate_score(&book_payload, &score_scale)?;
+    let key = BookKey::from_id(&id).map_err(warp::reject::custom)?;
+    let key = key.at_versions(if_match_versions(if_match.as_deref()));
+    set_book_score(&key, &book_payload, &causal, book_scores_mgr).await
+}
+
+// Add or replace the reviewer's score sub-record in back-end DB, for the book with the key
+//
+async fn set_book_score(
+    key: &BookKey, book_payload: &BookPayload, causal: &CausalContext,
+    book_scores_mgr: Arc<dyn BookScoresStore>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let book = book_payload_to_book(book_payload);
+
+    match book_scores_mgr.db_update_book_score(key, &book, causal).await {
+        Ok(counts) if counts.is_unmatched() => {
+            Err(unmatched_book(key, book_not_found(key), causal, &book_scores_mgr).await)
+        }
+        Ok(_) => Ok(causal_reply(
+            warp::reply::with_status(
+                "Updated existing review score for book",
+                http::StatusCode::OK,
+            ),
+            causal,
+        )),
+        Err(e) => {
+            eprintln!("Error updating data: {}", e);
+            Err(warp::reject::custom(e))
+        }
+    }
+}
+
+// Change the rating of the reviewer's existing score sub-record in back-end DB, for the book with
+// the id, if the book is still at a version the request requires
+//
+async fn patch_book_score_item(
+    id: String, book_payload: BookPayload, if_match: Option<String>, causal: CausalContext,
+    book_scores_mgr: Arc<dyn BookScoresStore>, score_scale: ScoreScale,
 ) -> Result<impl warp::Reply, warp::Rejection> {
-    match book_scores_mgr.db_update_book_score(&book_payload_to_book(&book_payload)).await {
-        Ok(_) => Ok(warp::reply::with_status(
-            "Updated existing review score for book",
-            http::StatusCode::CREATED,
+    validate_score(&book_payload, &score_scale)?;
+    let key = BookKey::from_id(&id).map_err(warp::reject::custom)?;
+    let key = key.at_versions(if_match_versions(if_match.as_deref()));
+
+    let book = book_payload_to_book(&book_payload);
+
+    match book_scores_mgr.db_change_book_score(&key, &book, &causal).await {
+        Ok(counts) if counts.is_unmatched() => {
+            let not_found = score_not_found(&book_payload.reference, &key);
+            Err(unmatched_book(&key, not_found, &causal, &book_scores_mgr).await)
+        }
+        Ok(_) => Ok(causal_reply(
+            warp::reply::with_status(
+                "Changed existing review score for book",
+                http::StatusCode::OK,
+            ),
+            &causal,
         )),
         Err(e) => {
-            eprintln!("Error updating data: {:#?}", e);
-            Err(warp::reject())
+            eprintln!("Error updating data: {}", e);
+            Err(warp::reject::custom(e))
         }
     }
 }
 
-// Find all book scores sub-records from back-end DB
+// Find all book scores sub-records from back-end DB, along with the entity tag of the book's
+// version, or if no title is provided, summarize the scores of all matching books, only returning
+// the requested fields, unless the client already has the same version of the book or summaries
 //
 async fn get_book_score(
-    book_payload: BookPayload, book_scores_mgr: BookScoresMgr,
+    books_query: BooksQuery, conditions: ReadConditions, causal: CausalContext,
+    book_scores_mgr: Arc<dyn BookScoresStore>,
 ) -> Result<impl warp::Reply, warp::Rejection> {
-    match book_scores_mgr.db_find_book_scores(&book_payload_to_book(&book_payload)).await {
-        Ok(result) => Ok(warp::reply::json(&book_to_book_payload(&result))),
+    let fields = query_to_fields(books_query.fields.as_deref()).map_err(warp::reject::custom)?;
+
+    if books_query.title.is_none() {
+        return summarize_book_scores(&books_query, &fields, &conditions, &causal, book_scores_mgr)
+            .await;
+    }
+
+    let key = books_query_to_key(&books_query).map_err(warp::reject::custom)?;
+    let book = find_book_scores(&key, &fields, &causal, book_scores_mgr).await?;
+    let version = book.as_ref().map(|book| Version::of_book(book.version, book.last_modified));
+    let payload = fields
+        .narrow_derived(&book_to_book_payload(&book), payload_field)
+        .map_err(warp::reject::custom)?;
+    let reply = versioned_reply(warp::reply::json(&payload), version.as_ref(), &conditions);
+    Ok(causal_reply(reply, &causal))
+}
+
+// Find book and all its scores sub-records from back-end DB, for API v2, along with the entity tag
+// of the book's version, or if no title is provided, summarize the scores of all matching books,
+// only returning the requested fields, unless the client already has the same version of the book
+// or summaries
+//
+async fn get_book_scores_v2(
+    books_query: BooksQuery, conditions: ReadConditions, causal: CausalContext,
+    book_scores_mgr: Arc<dyn BookScoresStore>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let fields = query_to_fields(books_query.fields.as_deref()).map_err(warp::reject::custom)?;
+
+    if books_query.title.is_none() {
+        return summarize_book_scores(&books_query, &fields, &conditions, &causal, book_scores_mgr)
+            .await;
+    }
+
+    let key = books_query_to_key(&books_query).map_err(warp::reject::custom)?;
+
+    match find_book_scores(&key, &fields, &causal, book_scores_mgr).await? {
+        Some(book) => {
+            let payload = fields
+                .narrow_derived(&book_to_book_scores_payload(&book), payload_field)
+                .map_err(warp::reject::custom)?;
+            let version = Version::of_book(book.version, book.last_modified);
+            let reply = versioned_reply(warp::reply::json(&payload), Some(&version), &conditions);
+            Ok(causal_reply(reply, &causal))
+        }
+        None => Err(book_not_found(&key)),
+    }
+}
+
+// Find book with the id and its scores sub-records from back-end DB, for API v1, along with the
+// entity tag of the book's version, only returning the requested fields, unless the client already
+// has the same version of the book
+//
+async fn get_book_score_item(
+    id: String, book_query: BookQuery, conditions: ReadConditions, causal: CausalContext,
+    book_scores_mgr: Arc<dyn BookScoresStore>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let fields = query_to_fields(book_query.fields.as_deref()).map_err(warp::reject::custom)?;
+    let key = BookKey::from_id(&id).map_err(warp::reject::custom)?;
+
+    match find_book_scores(&key, &fields, &causal, book_scores_mgr).await? {
+        Some(book) => {
+            let version = Version::of_book(book.version, book.last_modified);
+            let payload = fields
+                .narrow_derived(&book_to_book_payload(&Some(book)), payload_field)
+                .map_err(warp::reject::custom)?;
+            let reply = versioned_reply(warp::reply::json(&payload), Some(&version), &conditions);
+            Ok(causal_reply(reply, &causal))
+        }
+        None => Err(book_not_found(&key)),
+    }
+}
+
+// Find book with the id and all its scores sub-records from back-end DB, for API v2, along with the
+// entity tag of the book's version, only returning the requested fields, unless the client already
+// has the same version of the book
+//
+async fn get_book_scores_v2_item(
+    id: String, book_query: BookQuery, conditions: ReadConditions, causal: CausalContext,
+    book_scores_mgr: Arc<dyn BookScoresStore>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let fields = query_to_fields(book_query.fields.as_deref()).map_err(warp::reject::custom)?;
+    let key = BookKey::from_id(&id).map_err(warp::reject::custom)?;
+
+    match find_book_scores(&key, &fields, &causal, book_scores_mgr).await? {
+        Some(book) => {
+            let payload = fields
+                .narrow_derived(&book_to_book_scores_payload(&book), payload_field)
+                .map_err(warp::reject::custom)?;
+            let version = Version::of_book(book.version, book.last_modified);
+            let reply = versioned_reply(warp::reply::json(&payload), Some(&version), &conditions);
+            Ok(causal_reply(reply, &causal))
+        }
+        None => Err(book_not_found(&key)),
+    }
+}
+
+// Find the book with the key and its scores sub-records from back-end DB, if recorded, always
+// reading the book's version & time of last modification
+//
+async fn find_book_scores(
+    key: &BookKey, fields: &FieldSelection, causal: &CausalContext,
+    book_scores_mgr: Arc<dyn BookScoresStore>,
+) -> Result<Option<Book>, warp::Rejection> {
+    let mut projected = fields.clone();
+    projected.add("last_modified");
+    projected.add("version");
+    book_scores_mgr.db_find_book_scores(key, &projected, causal).await.map_err(|e| {
+        eprintln!("Error finding data: {}", e);
+        warp::reject::custom(e)
+    })
+}
+
+// Summarize the scores of all books in back-end DB matching the query's author & year, ordered by
+// average score if requested, only returning the requested fields, unless the client already has
+// the same version of the summaries
+//
+async fn summarize_book_scores(
+    books_query: &BooksQuery, fields: &FieldSelection, conditions: &ReadConditions,
+    causal: &CausalContext, book_scores_mgr: Arc<dyn BookScoresStore>,
+) -> Result<warp::reply::Response, warp::Rejection> {
+    let filter = books_query_to_filter(books_query).map_err(warp::reject::custom)?;
+
+    match book_scores_mgr.db_summarize_book_scores(&filter, causal).await {
+        Ok(results) => {
+            let payload = fields
+                .narrow_derived(&summaries_to_summaries_payload(&results), payload_field)
+                .map_err(warp::reject::custom)?;
+            let last_modified = results.iter().filter_map(|summary| summary.last_modified).max();
+            let version =
+                Version::of_listing(&payload, last_modified).map_err(warp::reject::custom)?;
+            let reply = versioned_reply(warp::reply::json(&payload), Some(&version), conditions);
+            Ok(causal_reply(reply, causal))
+        }
         Err(e) => {
-            eprintln!("Error finding data: {}", e.to_string());
-            Err(warp::reject())
+            eprintln!("Error finding data: {}", e);
+            Err(warp::reject::custom(e))
+        }
+    }
+}
+
+// Find a book's individual scores sub-records from back-end DB, optionally filtered by reviewer
+// reference and sorted by rating
+//
+async fn get_book_scores_list(
+    scores_query: ScoresQuery, causal: CausalContext, book_scores_mgr: Arc<dyn BookScoresStore>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let sort = scores_query.sort.as_deref().map(ScoreSort::parse).transpose();
+    let sort = sort.map_err(warp::reject::custom)?;
+    let key = BookKey::from_title_author(scores_query.title.as_ref(), scores_query.author.as_ref());
+    let key = key.map_err(warp::reject::custom)?;
+
+    let fields = FieldSelection::all(OWNED_FIELDS);
+
+    match find_book_scores(&key, &fields, &causal, book_scores_mgr).await? {
+        Some(result) => {
+            let mut scores = result.scores.unwrap_or_default();
+
+            if let Some(reference) = &scores_query.reference {
+                scores.retain(|score| score.reference.as_ref() == Some(reference));
+            }
+
+            if let Some(sort) = sort {
+                sort.apply(&mut scores);
+            }
+
+            Ok(causal_reply(warp::reply::json(&scores_to_scores_payload(&scores)), &causal))
         }
+        None => Err(book_not_found(&key)),
     }
 }
 
-// Delete specific book score sub-record from back-end DB
+// Delete specific book score sub-record from back-end DB, identified by the payload's title &
+// author and reviewer reference, if the book is still at a version the request requires
 //
 async fn delete_book_score(
-    book_payload: BookPayload, book_scores_mgr: BookScoresMgr,
+    book_payload: BookPayload, if_match: Option<String>, causal: CausalContext,
+    book_scores_mgr: Arc<dyn BookScoresStore>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let key = book_payload_to_key(&book_payload).map_err(warp::reject::custom)?;
+    let key = key.at_versions(if_match_versions(if_match.as_deref()));
+    delete_score(&key, &book_payload, &causal, book_scores_mgr).await
+}
+
+// Delete the reviewer reference's score sub-record from back-end DB, for the book with the id, if
+// the book is still at a version the request requires
+//
+async fn delete_book_score_item(
+    id: String, score_query: ScoreQuery, if_match: Option<String>, causal: CausalContext,
+    book_scores_mgr: Arc<dyn BookScoresStore>,
 ) -> Result<impl warp::Reply, warp::Rejection> {
-    match book_scores_mgr.db_delete_book_scores(&book_payload_to_book(&book_payload)).await {
-        Ok(_) => {
-            Ok(warp::reply::with_status("Removed review score for book", http::StatusCode::OK))
+    let key = BookKey::from_id(&id).map_err(warp::reject::custom)?;
+    let key = key.at_versions(if_match_versions(if_match.as_deref()));
+    let reference = score_query
+        .reference
+        .ok_or_else(|| warp::reject::custom(AppError::MissingField(String::from("reference"))))?;
+    let book_payload = BookPayload {
+        title: None,
+        author: None,
+        year: None,
+        reference: Some(reference),
+        score: None,
+    };
+    delete_score(&key, &book_payload, &causal, book_scores_mgr).await
+}
+
+// Delete the payload's reviewer reference's score sub-record from back-end DB, for the book with
+// the key
+//
+async fn delete_score(
+    key: &BookKey, book_payload: &BookPayload, causal: &CausalContext,
+    book_scores_mgr: Arc<dyn BookScoresStore>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let book = book_payload_to_book(book_payload);
+
+    match book_scores_mgr.db_delete_book_scores(key, &book, causal).await {
+        Ok(counts) if counts.is_unmatched() => {
+            let not_found = score_not_found(&book_payload.reference, key);
+            Err(unmatched_book(key, not_found, causal, &book_scores_mgr).await)
         }
+        Ok(_) => Ok(causal_reply(
+            warp::reply::with_status(warp::reply(), http::StatusCode::NO_CONTENT),
+            causal,
+        )),
         Err(e) => {
-            eprintln!("Error deleting data: {}", e.to_string());
-            Err(warp::reject())
+            eprintln!("Error deleting data: {}", e);
+            Err(warp::reject::custom(e))
+        }
+    }
+}
+
+// Check any score provided in the payload conforms to the score scale
+//
+fn validate_score(
+    book_payload: &BookPayload, score_scale: &ScoreScale,
+) -> Result<(), warp::Rejection> {
+    match book_payload.score {
+        Some(score) => score_scale.validate(score).map_err(warp::reject::custom),
+        None => Ok(()),
+    }
+}
+
+// Build rejection for a request targeting a book which is not recorded
+//
+fn book_not_found(key: &BookKey) -> warp::Rejection {
+    warp::reject::custom(AppError::NotFound(format!("No book {} recorded", key)))
+}
+
+// Build rejection for a request targeting a reviewer's score which is not recorded for a book
+//
+fn score_not_found(reference: &Option<String>, key: &BookKey) -> warp::Rejection {
+    warp::reject::custom(AppError::NotFound(format!(
+        "No review score from reference `{}` recorded for book {}",
+        reference.as_deref().unwrap_or_default(),
+        key
+    )))
+}
+
+// Build rejection for a write which matched no book, reporting whether the book is recorded but no
+// longer at a version the request requires, or otherwise the given not found rejection
+//
+async fn unmatched_book(
+    key: &BookKey, not_found: warp::Rejection, causal: &CausalContext,
+    book_scores_mgr: &Arc<dyn BookScoresStore>,
+) -> warp::Rejection {
+    if !key.has_versions() {
+        return not_found;
+    }
+
+    let fields = FieldSelection::all(&["version"]);
+
+    match book_scores_mgr.db_find_book_scores(&key.any_version(), &fields, causal).await {
+        Ok(Some(book)) if !key.matches_version(book.version) => {
+            warp::reject::custom(AppError::PreconditionFailed(format!(
+                "Book {} has been modified since the version the request requires",
+                key
+            )))
+        }
+        Ok(_) => not_found,
+        Err(e) => {
+            eprintln!("Error finding data: {}", e);
+            warp::reject::custom(e)
         }
     }
 }
@@ -146,51 +736,484 @@ async fn delete_book_score(
 // Take contents of Book payload and put into Book record to be passed to DB tier
 //
 fn book_payload_to_book(book_payload: &BookPayload) -> Book {
-    let rating = book_payload.score.map(|val| val as i32);
-    let scores = Some(vec![Score { reference: book_payload.reference.clone(), rating }]);
+    let scores =
+        Some(vec![Score { reference: book_payload.reference.clone(), rating: book_payload.score }]);
     Book {
+        id: None,
         title: book_payload.title.clone(),
         author: book_payload.author.clone(),
         year: book_payload.year,
         scores,
         last_modified: None,
+        version: None,
     }
 }
 
-// Build response Books payload based on book records returned from DB tier
+// Take title & author of Book payload and put into the key identifying the book to the DB tier,
+// for requests which predate book ids
+//
+fn book_payload_to_key(book_payload: &BookPayload) -> Result<BookKey, AppError> {
+    BookKey::from_title_author(book_payload.title.as_ref(), book_payload.author.as_ref())
+}
+
+// Take title & author of the query string and put into the key identifying the book to the DB tier
+//
+fn books_query_to_key(books_query: &BooksQuery) -> Result<BookKey, AppError> {
+    BookKey::from_title_author(books_query.title.as_ref(), books_query.author.as_ref())
+}
+
+// Take browsing fields of the query string and put into Book scores filter to be passed to DB tier,
+// returning an error if the sort order or limit is invalid
 //
-fn book_to_book_payload(optional_book: &Option<Book>) -> BookPayload {
+fn books_query_to_filter(books_query: &BooksQuery) -> Result<BookScoresFilter, AppError> {
+    if books_query.limit == Some(0) {
+        return Err(AppError::InvalidField {
+            field: String::from("limit"),
+            reason: String::from("must be greater than 0"),
+        });
+    }
+
+    Ok(BookScoresFilter {
+        author: books_query.author.clone(),
+        year: books_query.year,
+        sort: books_query.sort.as_deref().map(ScoreSort::parse).transpose()?,
+        limit: books_query.limit,
+    })
+}
+
+// Take the fields list of the query string and put into the field selection to read & return,
+// returning an error if any field is not owned by app2
+//
+fn query_to_fields(fields: Option<&str>) -> Result<FieldSelection, AppError> {
+    match fields {
+        Some(fields) => FieldSelection::parse(fields, OWNED_FIELDS),
+        None => Ok(FieldSelection::all(OWNED_FIELDS)),
+    }
+}
+
+// Name of the book record field which a Book scores payload key is derived from, where all the
+// score statistics & notes are derived from the scores field
+//
+fn payload_field(key: &str) -> &str {
+    match key {
+        "title" | "author" | "year" | "last_modified" => key,
+        _ => "scores",
+    }
+}
+
+// Build response Books payload based on book records returned from DB tier, where the score is the
+// average across all valid review scores
+//
+fn book_to_book_payload(optional_book: &Option<Book>) -> BookScoresV1Payload {
     match optional_book {
         Some(book) => {
-            let avg_score = match &book.scores {
-                Some(scores) => {
-                    let res = scores.iter().map(|score| score.rating.unwrap_or(0)).sum::<i32>()
-                        as f32
-                        / scores.len() as f32;
-
-                    if res.is_nan() {
-                        None
-                    } else {
-                        Some(res)
-                    }
-                }
-                None => None,
-            };
-
-            let note = Some(String::from(if avg_score.is_some() {
+            let stats = score_stats(book.scores.as_deref().unwrap_or_default());
+            let note = Some(String::from(if stats.mean.is_some() {
                 "Average score accross all reviews"
             } else {
                 "No scores recorded"
             }));
 
-            BookPayload {
+            BookScoresV1Payload {
+                id: book.id.map(|id| id.to_hex()),
                 title: book.title.clone(),
                 author: book.author.clone(),
                 year: book.year,
                 reference: note,
-                score: avg_score,
+                score: stats.mean,
+                median_score: stats.median,
+                min_score: stats.min,
+                max_score: stats.max,
+                scores_counted: Some(stats.counted),
+                scores_ignored: Some(stats.ignored),
+                last_modified: book.last_modified.map(|time| time.to_rfc3339_string()),
             }
         }
-        None => BookPayload { title: None, author: None, year: None, reference: None, score: None },
+        None => BookScoresV1Payload {
+            id: None,
+            title: None,
+            author: None,
+            year: None,
+            reference: None,
+            score: None,
+            median_score: None,
+            min_score: None,
+            max_score: None,
+            scores_counted: None,
+            scores_ignored: None,
+            last_modified: None,
+        },
+    }
+}
+
+// Build API v2 response Book scores payload based on book record returned from DB tier
+//
+fn book_to_book_scores_payload(book: &Book) -> BookScoresPayload {
+    let scores = book.scores.as_deref().unwrap_or_default();
+    let stats = score_stats(scores);
+    let note = String::from(if stats.mean.is_some() {
+        "Average score across all valid reviews"
+    } else {
+        "No scores recorded"
+    });
+
+    BookScoresPayload {
+        id: book.id.map(|id| id.to_hex()),
+        title: book.title.clone(),
+        author: book.author.clone(),
+        year: book.year,
+        average_score: stats.mean,
+        median_score: stats.median,
+        min_score: stats.min,
+        max_score: stats.max,
+        scores_counted: stats.counted,
+        scores_ignored: stats.ignored,
+        note,
+        scores: scores_to_scores_payload(scores),
+        last_modified: book.last_modified.map(|time| time.to_rfc3339_string()),
+    }
+}
+
+// Build response individual Scores payload based on score sub-records returned from DB tier
+//
+fn scores_to_scores_payload(scores: &[Score]) -> Vec<ScorePayload> {
+    scores
+        .iter()
+        .map(|score| ScorePayload { reference: score.reference.clone(), rating: score.rating })
+        .collect()
+}
+
+// Build response Book scores summaries payload based on summaries returned from DB tier
+//
+fn summaries_to_summaries_payload(
+    summaries: &[BookScoresSummary],
+) -> Vec<BookScoresSummaryPayload> {
+    summaries
+        .iter()
+        .map(|summary| BookScoresSummaryPayload {
+            id: summary.id.map(|id| id.to_hex()),
+            title: summary.title.clone(),
+            author: summary.author.clone(),
+            year: summary.year,
+            average_score: summary.average_score,
+            scores_counted: summary.scores_counted,
+            last_modified: summary.last_modified.map(|time| time.to_rfc3339_string()),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mem_store::MemCollection;
+    use bson::doc;
+    use serde_json::{json, Value};
+    use warp::http::StatusCode;
+
+    const URL: &str = "/v1/books";
+    const URL_V2: &str = "/v2/books";
+    const BOOK_QUERY: &str = "title=Test%20Book&author=Test%20Writer";
+    const SCALE: ScoreScale = ScoreScale { min: 0.0, max: 10.0, step: 0.5 };
+
+    // Routes of app2 over a new in-memory books collection holding just one book, which has no
+    // scores yet
+    //
+    fn mem_routes() -> impl Filter<Extract = (impl warp::Reply,), Error = Infallible> + Clone {
+        let coll = MemCollection::default();
+        coll.insert_one(doc! {"title": "Test Book", "author": "Test Writer", "year": 2020})
+            .unwrap();
+        book_scores_routes(
+            Arc::new(MemBookScoresMgr::new(coll)),
+            16384,
+            DupScorePolicy::Reject,
+            SCALE,
+        )
+    }
+
+    // Request of the method with a score payload for the book from the reviewer reference
+    //
+    fn score_request(
+        method: &str, reference: &str, score: Option<f64>,
+    ) -> warp::test::RequestBuilder {
+        warp::test::request().method(method).path(URL).json(&json!({
+            "title": "Test Book",
+            "author": "Test Writer",
+            "reference": reference,
+            "score": score,
+        }))
+    }
+
+    // Read the JSON body of a GET of the path via the routes, asserting it succeeds
+    //
+    async fn get_json(
+        routes: &(impl Filter<Extract = (impl warp::Reply,), Error = Infallible> + Clone + 'static),
+        path: &str,
+    ) -> Value {
+        let response = warp::test::request().path(path).reply(routes).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        serde_json::from_slice(response.body()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn scores_averaged_in_both_api_versions() {
+        let routes = mem_routes();
+
+        for (reference, score) in [("Reviewer A", 10.0), ("Reviewer B", 9.0)] {
+            let response = score_request("POST", reference, Some(score)).reply(&routes).await;
+            assert!(response.status().is_success());
+        }
+
+        let book = get_json(&routes, &format!("{}?{}", URL, BOOK_QUERY)).await;
+        assert_eq!(book["score"], 9.5);
+        assert_eq!(book["scores_counted"], 2);
+        assert_eq!((&book["min_score"], &book["max_score"]), (&json!(9.0), &json!(10.0)));
+        let book = get_json(&routes, &format!("{}?{}", URL_V2, BOOK_QUERY)).await;
+        assert_eq!(book["average_score"], 9.5);
+        assert_eq!(
+            book["scores"],
+            json!([
+                {"reference": "Reviewer A", "rating": 10.0},
+                {"reference": "Reviewer B", "rating": 9.0},
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn malformed_scores_skipped() {
+        let coll = MemCollection::default();
+        let scores = vec![doc! {"reference": "Reviewer A", "rating": 6.0}, doc! {"reference": "B"}];
+        coll.insert_one(doc! {"title": "Test Book", "author": "Test Writer", "scores": scores})
+            .unwrap();
+        let routes = book_scores_routes(
+            Arc::new(MemBookScoresMgr::new(coll)),
+            16384,
+            DupScorePolicy::Reject,
+            SCALE,
+        );
+        let book = get_json(&routes, &format!("{}?{}", URL, BOOK_QUERY)).await;
+        assert_eq!((&book["score"], &book["median_score"]), (&json!(6.0), &json!(6.0)));
+        assert_eq!((&book["scores_counted"], &book["scores_ignored"]), (&json!(1), &json!(1)));
+    }
+
+    #[tokio::test]
+    async fn missing_score_rejected() {
+        let routes = mem_routes();
+        let response = score_request("POST", "Reviewer A", None).reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let error: Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!((&error["code"], &error["field"]), (&json!("missing_field"), &json!("score")));
+    }
+
+    #[tokio::test]
+    async fn invalid_scores_rejected() {
+        let routes = mem_routes();
+
+        for score in [11.0, -0.5, 9.25] {
+            let response = score_request("POST", "Reviewer A", Some(score)).reply(&routes).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            let error: Value = serde_json::from_slice(response.body()).unwrap();
+            assert_eq!(
+                (&error["code"], &error["field"]),
+                (&json!("invalid_field"), &json!("score"))
+            );
+        }
+
+        let book = get_json(&routes, &format!("{}?{}", URL, BOOK_QUERY)).await;
+        assert_eq!(book["score"], Value::Null);
+    }
+
+    #[tokio::test]
+    async fn duplicate_score_rejected_but_update_replaces_score() {
+        let routes = mem_routes();
+        score_request("POST", "Reviewer A", Some(9.0)).reply(&routes).await;
+        let response = score_request("POST", "Reviewer A", Some(1.0)).reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let response = score_request("PUT", "Reviewer A", Some(8.0)).reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let book = get_json(&routes, &format!("{}?{}", URL, BOOK_QUERY)).await;
+        assert_eq!(book["score"], 8.0);
+    }
+
+    #[tokio::test]
+    async fn duplicate_score_replaced_by_upsert_policy() {
+        let coll = MemCollection::default();
+        coll.insert_one(doc! {"title": "Test Book", "author": "Test Writer"}).unwrap();
+        let routes = book_scores_routes(
+            Arc::new(MemBookScoresMgr::new(coll)),
+            16384,
+            DupScorePolicy::Upsert,
+            SCALE,
+        );
+
+        for score in [9.0, 7.0] {
+            let response = score_request("POST", "Reviewer A", Some(score)).reply(&routes).await;
+            assert_eq!(response.status(), StatusCode::CREATED);
+        }
+
+        let book = get_json(&routes, &format!("{}?{}", URL, BOOK_QUERY)).await;
+        assert_eq!(book["score"], 7.0);
+    }
+
+    #[tokio::test]
+    async fn individual_scores_sorted_and_filtered_by_reference() {
+        let routes = mem_routes();
+
+        for (reference, score) in [("Reviewer A", 6.0), ("Reviewer B", 9.0), ("Reviewer C", 7.5)] {
+            score_request("POST", reference, Some(score)).reply(&routes).await;
+        }
+
+        let path = format!("{}/scores?{}&sort=-rating", URL_V2, BOOK_QUERY);
+        let scores = get_json(&routes, &path).await;
+        let ratings: Vec<&Value> =
+            scores.as_array().unwrap().iter().map(|s| &s["rating"]).collect();
+        assert_eq!(ratings, [9.0, 7.5, 6.0]);
+        let path = format!("{}/scores?{}&reference=Reviewer%20C", URL, BOOK_QUERY);
+        let scores = get_json(&routes, &path).await;
+        assert_eq!(scores, json!([{"reference": "Reviewer C", "rating": 7.5}]));
+        let path = format!("{}/scores?{}&sort=reference", URL, BOOK_QUERY);
+        let response = warp::test::request().path(&path).reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn score_summaries_of_all_books_ordered_by_rating() {
+        let coll = MemCollection::default();
+
+        for (title, author, rating) in [
+            ("Book A", "Test Writer", Some(6.0)),
+            ("Book B", "Test Writer", None),
+            ("Book C", "Test Writer", Some(9.0)),
+            ("Book D", "Other Writer", Some(10.0)),
+        ] {
+            let scores: Vec<_> = rating
+                .map(|rating| doc! {"reference": "Reviewer", "rating": rating})
+                .into_iter()
+                .collect();
+            coll.insert_one(
+                doc! {"title": title, "author": author, "year": 2020, "scores": scores},
+            )
+            .unwrap();
+        }
+
+        let routes = book_scores_routes(
+            Arc::new(MemBookScoresMgr::new(coll)),
+            16384,
+            DupScorePolicy::Reject,
+            SCALE,
+        );
+        let books = get_json(&routes, &format!("{}?author=Test%20Writer&sort=-rating", URL)).await;
+        let titles: Vec<&Value> = books.as_array().unwrap().iter().map(|b| &b["title"]).collect();
+        assert_eq!(titles, ["Book C", "Book A", "Book B"]);
+        assert_eq!(
+            (&books[0]["average_score"], &books[0]["scores_counted"]),
+            (&json!(9.0), &json!(1))
+        );
+        let books = get_json(&routes, &format!("{}?sort=rating&limit=2", URL_V2)).await;
+        let titles: Vec<&Value> = books.as_array().unwrap().iter().map(|b| &b["title"]).collect();
+        assert_eq!(titles, ["Book A", "Book C"]);
+    }
+
+    #[tokio::test]
+    async fn book_scores_narrowed_to_selected_fields() {
+        let routes = mem_routes();
+        let response = score_request("POST", "Reviewer A", Some(8.0)).reply(&routes).await;
+        assert!(response.status().is_success());
+        let book = get_json(&routes, &format!("{}?{}&fields=title", URL, BOOK_QUERY)).await;
+        assert_eq!(book.as_object().unwrap().keys().collect::<Vec<_>>(), ["id", "title"]);
+        let book = get_json(&routes, &format!("{}?{}&fields=scores", URL_V2, BOOK_QUERY)).await;
+        assert_eq!(book["average_score"], 8.0);
+        assert!(book.get("title").is_none());
+        let book = get_json(&routes, &format!("{}?{}&fields=last_modified", URL, BOOK_QUERY)).await;
+        assert!(bson::DateTime::parse_rfc3339_str(book["last_modified"].as_str().unwrap()).is_ok());
+        let books = get_json(&routes, &format!("{}?fields=author", URL)).await;
+        assert_eq!(books[0]["author"], "Test Writer");
+        assert!(books[0].get("title").is_none());
+
+        let path = format!("{}?{}&fields=title,quantity", URL, BOOK_QUERY);
+        let response = warp::test::request().path(&path).reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let error: Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(error["field"], "fields");
+    }
+
+    #[tokio::test]
+    async fn scores_changed_and_removed_by_book_id() {
+        let routes = mem_routes();
+        score_request("POST", "Reviewer A", Some(9.0)).reply(&routes).await;
+        let book = get_json(&routes, &format!("{}?{}&fields=title", URL_V2, BOOK_QUERY)).await;
+        let path = format!("{}/{}", URL_V2, book["id"].as_str().unwrap());
+
+        for (method, reference, score) in [("PATCH", "Reviewer A", 7.0), ("PUT", "Reviewer B", 5.0)]
+        {
+            let response = warp::test::request()
+                .method(method)
+                .path(&path)
+                .json(&json!({"reference": reference, "score": score}))
+                .reply(&routes)
+                .await;
+            assert!(response.status().is_success());
+        }
+
+        let book = get_json(&routes, &path).await;
+        assert_eq!(
+            book["scores"],
+            json!([
+                {"reference": "Reviewer A", "rating": 7.0},
+                {"reference": "Reviewer B", "rating": 5.0},
+            ])
+        );
+        let path_a = format!("{}?reference=Reviewer%20A", path);
+        let response = warp::test::request().method("DELETE").path(&path_a).reply(&routes).await;
+        assert!(response.status().is_success());
+        let book = get_json(&routes, &path).await;
+        assert_eq!(book["scores"], json!([{"reference": "Reviewer B", "rating": 5.0}]));
+    }
+
+    #[tokio::test]
+    async fn score_for_unknown_book_not_found() {
+        let response = warp::test::request()
+            .method("POST")
+            .path(URL)
+            .json(&json!({"title": "No Book", "author": "No Writer", "reference": "A", "score": 5}))
+            .reply(&mem_routes())
+            .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn score_only_deleted_at_current_etag() {
+        let routes = mem_routes();
+        score_request("POST", "Reviewer A", Some(9.0)).reply(&routes).await;
+        let book = get_json(&routes, &format!("{}?{}&fields=title", URL_V2, BOOK_QUERY)).await;
+        let path = format!("{}/{}?reference=Reviewer%20A", URL, book["id"].as_str().unwrap());
+        let response = warp::test::request().path(&path).reply(&routes).await;
+        let etag = response.headers()[http::header::ETAG].to_str().unwrap().to_string();
+        let delete = |if_match: &str| {
+            warp::test::request()
+                .method("DELETE")
+                .path(&path)
+                .header(http::header::IF_MATCH, if_match)
+        };
+        let response = delete("\"0\"").reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+        let response = delete(&etag).reply(&routes).await;
+        assert!(response.status().is_success());
+        let book = get_json(&routes, &format!("{}?{}", URL_V2, BOOK_QUERY)).await;
+        assert_eq!(book["scores"], json!([]));
+    }
+
+    #[tokio::test]
+    async fn unchanged_scores_not_resent() {
+        let routes = mem_routes();
+        score_request("POST", "Reviewer A", Some(9.0)).reply(&routes).await;
+        let path = format!("{}?{}", URL_V2, BOOK_QUERY);
+        let response = warp::test::request().path(&path).reply(&routes).await;
+        let etag = response.headers()[http::header::ETAG].to_str().unwrap().to_string();
+        let read = || warp::test::request().path(&path).header(http::header::IF_NONE_MATCH, &etag);
+        let response = read().reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert!(response.body().is_empty());
+        score_request("PUT", "Reviewer A", Some(8.0)).reply(&routes).await;
+        assert_eq!(read().reply(&routes).await.status(), StatusCode::OK);
     }
 }
@@ -0,0 +1,227 @@
+use std::cmp::Ordering;
+
+use super::db::Score;
+use crate::error::AppError;
+
+const STEP_TOLERANCE: f64 = 1e-9;
+
+// Range & granularity which review scores submitted to app2 must conform to (eg. 0 to 10 in steps
+// of 0.5), where a step of zero allows any value in the range
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScoreScale {
+    pub min: f64,
+    pub max: f64,
+    pub step: f64,
+}
+
+impl ScoreScale {
+    // Check a submitted score falls within the range and on one of the scale's steps, returning an
+    // error naming the offending payload field if not
+    //
+    pub fn validate(&self, score: f64) -> Result<(), AppError> {
+        let in_range = score.is_finite() && score >= self.min && score <= self.max;
+        let on_step = self.step == 0.0 || {
+            let steps = (score - self.min) / self.step;
+            (steps - steps.round()).abs() < STEP_TOLERANCE
+        };
+
+        if in_range && on_step {
+            Ok(())
+        } else {
+            Err(AppError::InvalidField { field: String::from("score"), reason: self.to_string() })
+        }
+    }
+}
+
+impl std::fmt::Display for ScoreScale {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "must be between {} and {}", self.min, self.max)?;
+
+        if self.step > 0.0 {
+            write!(f, " in steps of {}", self.step)?;
+        }
+
+        Ok(())
+    }
+}
+
+// Summary statistics for a book's review scores, only counting scores which have a valid rating
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ScoreStats {
+    pub counted: usize,
+    pub ignored: usize,
+    pub mean: Option<f64>,
+    pub median: Option<f64>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+// Calculate statistics across a book's review scores, skipping (but counting) any malformed
+// score which has no rating, rather than treating it as a zero rating
+//
+pub fn score_stats(scores: &[Score]) -> ScoreStats {
+    let mut ratings: Vec<f64> = scores.iter().filter_map(|score| score.rating).collect();
+    let ignored = scores.len() - ratings.len();
+
+    if ratings.is_empty() {
+        return ScoreStats { ignored, ..ScoreStats::default() };
+    }
+
+    ratings.sort_by(f64::total_cmp);
+    let counted = ratings.len();
+    let mid = counted / 2;
+    let median = if counted.is_multiple_of(2) {
+        (ratings[mid - 1] + ratings[mid]) / 2.0
+    } else {
+        ratings[mid]
+    };
+
+    ScoreStats {
+        counted,
+        ignored,
+        mean: Some(ratings.iter().sum::<f64>() / counted as f64),
+        median: Some(median),
+        min: ratings.first().copied(),
+        max: ratings.last().copied(),
+    }
+}
+
+// Order in which to list a book's individual review scores
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScoreSort {
+    RatingAscending,
+    RatingDescending,
+}
+
+impl ScoreSort {
+    // Parse the value of a 'sort' query parameter ('rating' or '-rating' for descending order)
+    //
+    pub fn parse(sort: &str) -> Result<Self, AppError> {
+        match sort {
+            "rating" => Ok(Self::RatingAscending),
+            "-rating" => Ok(Self::RatingDescending),
+            _ => Err(AppError::InvalidField {
+                field: String::from("sort"),
+                reason: String::from("must be 'rating' or '-rating'"),
+            }),
+        }
+    }
+
+    // Direction of the order as used in a MongoDB sort specification
+    //
+    pub fn direction(&self) -> i32 {
+        match self {
+            Self::RatingAscending => 1,
+            Self::RatingDescending => -1,
+        }
+    }
+
+    // Order two optional ratings, always placing a missing rating after a present one
+    //
+    pub fn compare(&self, rating1: Option<f64>, rating2: Option<f64>) -> Ordering {
+        match (rating1, rating2) {
+            (Some(rating1), Some(rating2)) => match self {
+                Self::RatingAscending => rating1.total_cmp(&rating2),
+                Self::RatingDescending => rating2.total_cmp(&rating1),
+            },
+            (rating1, rating2) => rating2.is_some().cmp(&rating1.is_some()),
+        }
+    }
+
+    // Sort scores by rating in place, always placing any scores with no valid rating last
+    //
+    pub fn apply(&self, scores: &mut [Score]) {
+        scores.sort_by(|score1, score2| self.compare(score1.rating, score2.rating));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCALE: ScoreScale = ScoreScale { min: 0.0, max: 10.0, step: 0.5 };
+
+    // Scores with the ratings, where a missing rating is a malformed score
+    //
+    fn scores(ratings: &[Option<f64>]) -> Vec<Score> {
+        ratings
+            .iter()
+            .map(|rating| Score { reference: Some(String::from("Reviewer")), rating: *rating })
+            .collect()
+    }
+
+    #[test]
+    fn stats_of_odd_number_of_scores() {
+        let stats = score_stats(&scores(&[Some(9.0), Some(1.0), Some(5.0)]));
+        assert_eq!(
+            stats,
+            ScoreStats {
+                counted: 3,
+                ignored: 0,
+                mean: Some(5.0),
+                median: Some(5.0),
+                min: Some(1.0),
+                max: Some(9.0),
+            }
+        );
+    }
+
+    #[test]
+    fn stats_of_even_number_of_scores_average_middle_two() {
+        let stats = score_stats(&scores(&[Some(10.0), Some(2.0), Some(4.0), Some(8.0)]));
+        assert_eq!(stats.median, Some(6.0));
+        assert_eq!(stats.mean, Some(6.0));
+        assert_eq!((stats.min, stats.max), (Some(2.0), Some(10.0)));
+    }
+
+    #[test]
+    fn stats_ignore_malformed_scores() {
+        let stats = score_stats(&scores(&[Some(8.0), None, Some(6.0), None]));
+        assert_eq!((stats.counted, stats.ignored), (2, 2));
+        assert_eq!((stats.mean, stats.median), (Some(7.0), Some(7.0)));
+    }
+
+    #[test]
+    fn stats_of_only_malformed_scores_are_empty() {
+        let stats = score_stats(&scores(&[None, None]));
+        assert_eq!(stats, ScoreStats { ignored: 2, ..ScoreStats::default() });
+        assert_eq!(score_stats(&[]), ScoreStats::default());
+    }
+
+    #[test]
+    fn scores_within_range_on_steps_accepted() {
+        for score in [0.0, 0.5, 7.5, 10.0] {
+            assert!(SCALE.validate(score).is_ok(), "{}", score);
+        }
+    }
+
+    #[test]
+    fn scores_out_of_range_or_between_steps_rejected() {
+        for score in [-0.5, 10.5, 7.25, f64::NAN, f64::INFINITY] {
+            let result = SCALE.validate(score);
+            assert!(
+                matches!(result, Err(AppError::InvalidField { field, .. }) if field == "score"),
+                "{}",
+                score
+            );
+        }
+    }
+
+    #[test]
+    fn steps_tolerate_float_rounding_and_start_at_min() {
+        let scale = ScoreScale { min: 0.0, max: 1.0, step: 0.1 };
+        assert!(scale.validate(0.3).is_ok());
+        assert!(scale.validate(0.7).is_ok());
+        assert!(scale.validate(0.35).is_err());
+        let scale = ScoreScale { min: 1.25, max: 5.0, step: 0.5 };
+        assert!(scale.validate(1.75).is_ok());
+        assert!(scale.validate(2.0).is_err());
+    }
+
+    #[test]
+    fn zero_step_allows_any_score_in_range() {
+        let scale = ScoreScale { step: 0.0, ..SCALE };
+        assert!(scale.validate(7.123).is_ok());
+        assert!(scale.validate(10.01).is_err());
+    }
+}
@@ -0,0 +1,170 @@
+use bson::Timestamp;
+use mongodb::options::SessionOptions;
+use mongodb::{Client, ClientSession, ClusterTime};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use warp::http::header::HeaderValue;
+use warp::reply::Response;
+use warp::{Filter, Reply};
+
+use crate::error::AppError;
+use crate::token::{decode_token, encode_token};
+
+// Header of a request carrying the causal token from the response to the client's previous
+// request, and of a response carrying the causal token of the request's latest operation
+pub const CAUSAL_TOKEN_HEADER: &str = "x-causal-token";
+
+// Times of a client's latest operation against MongoDB, which the client provides on its next
+// request (to either app) so that request observes the outcome of the operation, eg. reads the
+// client's own write, even when reading from a secondary
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+struct CausalToken {
+    operation_time: Timestamp,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cluster_time: Option<ClusterTime>,
+}
+
+impl CausalToken {
+    // Encode as an opaque token for clients to pass back on their next request
+    //
+    fn encode(&self) -> Result<String, AppError> {
+        encode_token(&bson::to_document(self)?)
+    }
+
+    // Decode a token previously provided to a client, returning an error naming the header if the
+    // token is not valid
+    //
+    fn decode(token: &str) -> Result<Self, AppError> {
+        decode_token(token).and_then(|doc| bson::from_document(doc).ok()).ok_or_else(|| {
+            AppError::InvalidField {
+                field: String::from(CAUSAL_TOKEN_HEADER),
+                reason: String::from("is not a causal token returned by a previous response"),
+            }
+        })
+    }
+}
+
+// Causal context of a request, starting from the causal token the client provides, if any, and
+// advanced by each of the request's operations which run in a causally consistent session
+#[derive(Debug, Clone, Default)]
+pub struct CausalContext {
+    token: Arc<Mutex<Option<CausalToken>>>,
+}
+
+impl CausalContext {
+    // Start a session for an operation, which, if causally consistent, observes the outcome of the
+    // operations of the client's earlier requests and of this request
+    //
+    pub async fn start_session(
+        &self, client: &Client, causal_consistency: bool,
+    ) -> Result<ClientSession, AppError> {
+        let options = SessionOptions::builder().causal_consistency(causal_consistency).build();
+        let mut session = client.start_session(Some(options)).await?;
+
+        if let (true, Some(token)) = (causal_consistency, self.token()) {
+            if let Some(cluster_time) = &token.cluster_time {
+                session.advance_cluster_time(cluster_time);
+            }
+
+            session.advance_operation_time(token.operation_time);
+        }
+
+        Ok(session)
+    }
+
+    // Advance to the times of the latest operation of a causally consistent session, which the
+    // deployment only reports if it's a replica set or sharded cluster
+    //
+    pub fn observe(&self, session: &ClientSession) {
+        let causal_consistency = session.options().and_then(|options| options.causal_consistency);
+
+        if let (Some(true), Some(operation_time)) = (causal_consistency, session.operation_time()) {
+            if let Ok(mut token) = self.token.lock() {
+                *token = Some(CausalToken {
+                    operation_time,
+                    cluster_time: session.cluster_time().cloned(),
+                });
+            }
+        }
+    }
+
+    // Current causal token, if any
+    //
+    fn token(&self) -> Option<CausalToken> {
+        self.token.lock().ok().and_then(|token| token.clone())
+    }
+}
+
+// Capture http causal token header of a request, returning an error if the token is not valid
+//
+pub fn capture_causal_context(
+) -> impl Filter<Extract = (CausalContext,), Error = warp::Rejection> + Clone {
+    warp::header::optional::<String>(CAUSAL_TOKEN_HEADER).and_then(
+        |token: Option<String>| async move {
+            let token = token.as_deref().map(CausalToken::decode).transpose();
+            let token = token.map_err(warp::reject::custom)?;
+            Ok::<_, warp::Rejection>(CausalContext { token: Arc::new(Mutex::new(token)) })
+        },
+    )
+}
+
+// Build the response of a request, labelling it with the causal token of the request's latest
+// operation, if any, for the client to provide on its next request
+//
+pub fn causal_reply(reply: impl Reply, causal: &CausalContext) -> Response {
+    let mut response = reply.into_response();
+    let token = causal.token().map(|token| token.encode()).transpose();
+
+    if let Ok(Some(token)) = token {
+        if let Ok(value) = HeaderValue::from_str(&token) {
+            response.headers_mut().insert(CAUSAL_TOKEN_HEADER, value);
+        }
+    }
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Causal token of an operation at the time
+    //
+    fn token_at(time: u32) -> CausalToken {
+        CausalToken { operation_time: Timestamp { time, increment: 1 }, cluster_time: None }
+    }
+
+    #[test]
+    fn tokens_decoded_as_encoded() {
+        let token = token_at(1700000000);
+        assert_eq!(CausalToken::decode(&token.encode().unwrap()).unwrap(), token);
+    }
+
+    #[test]
+    fn invalid_tokens_rejected_naming_header() {
+        let not_a_token = encode_token(&bson::doc! {"sort": "year"}).unwrap();
+
+        for token in ["", "zz", "0a1", &not_a_token] {
+            match CausalToken::decode(token) {
+                Err(AppError::InvalidField { field, .. }) => assert_eq!(field, CAUSAL_TOKEN_HEADER),
+                other => panic!("Expected invalid field error, not {:?}", other),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn provided_token_returned_until_advanced() {
+        let token = token_at(1700000000).encode().unwrap();
+        let causal = warp::test::request()
+            .header(CAUSAL_TOKEN_HEADER, &token)
+            .filter(&capture_causal_context())
+            .await
+            .unwrap();
+        let response = causal_reply(warp::reply(), &causal);
+        assert_eq!(response.headers()[CAUSAL_TOKEN_HEADER], token.as_str());
+
+        let causal = warp::test::request().filter(&capture_causal_context()).await.unwrap();
+        let response = causal_reply(warp::reply(), &causal);
+        assert!(!response.headers().contains_key(CAUSAL_TOKEN_HEADER));
+    }
+}
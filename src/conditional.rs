@@ -0,0 +1,152 @@
+use bson::DateTime;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::time::{Duration, UNIX_EPOCH};
+use warp::http::header::{
+    HeaderValue, ETAG, IF_MATCH, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+};
+use warp::http::StatusCode;
+use warp::reply::Response;
+use warp::{Filter, Reply};
+
+use crate::error::AppError;
+
+// Header value of If-Match or If-None-Match which matches a record at any version
+const ANY_ETAG: &str = "*";
+
+// Prefix of a weak entity tag
+const WEAK_ETAG_PREFIX: &str = "W/";
+
+// Version of a book record or listing returned by a read, identified by its entity tag and by the
+// time of its last modification, either of which may be unknown
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Version {
+    etag: Option<String>,
+    last_modified: Option<DateTime>,
+}
+
+impl Version {
+    // Version of a book record, where the entity tag is the count of writes to the record quoted,
+    // which every write of either app increments in the same atomic update (so, unlike a time,
+    // two writes never leave the same version), and a record without a count has no entity tag
+    //
+    pub fn of_book(version: Option<i64>, last_modified: Option<DateTime>) -> Self {
+        Self { etag: version.map(|version| format!("\"{}\"", version)), last_modified }
+    }
+
+    // Version of a listing of books, where the entity tag is a SHA-256 digest of the listing's
+    // response payload, so it changes when any book is added, changed or removed (and is stable
+    // across builds), and the time of last modification is the latest of the listed books, which
+    // doesn't reflect books removed from the listing
+    //
+    pub fn of_listing<T>(payload: &T, last_modified: Option<DateTime>) -> Result<Self, AppError>
+    where
+        T: Serialize,
+    {
+        let json = serde_json::to_vec(payload).map_err(|e| AppError::Internal(e.to_string()))?;
+        let etag = format!("\"{:x}\"", Sha256::digest(&json));
+        Ok(Self { etag: Some(etag), last_modified })
+    }
+}
+
+// Conditional http headers of a read, where the client only wants the response if the record or
+// listing has changed since the version it already has
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReadConditions {
+    if_none_match: Option<String>,
+    if_modified_since: Option<String>,
+}
+
+impl ReadConditions {
+    // Check whether the client already has the version, by its entity tag if the client provides
+    // any (using weak comparison), otherwise by whether it's been modified since the given time
+    // (to the nearest second), where an unparsable time is ignored
+    //
+    pub fn is_current(&self, version: &Version) -> bool {
+        if let Some(if_none_match) = &self.if_none_match {
+            let etag = version.etag.as_deref();
+            return if_none_match.split(',').map(str::trim).any(|tag| {
+                tag == ANY_ETAG || Some(tag.trim_start_matches(WEAK_ETAG_PREFIX)) == etag
+            });
+        }
+
+        match (&self.if_modified_since, version.last_modified) {
+            (Some(since), Some(last_modified)) => match httpdate::parse_http_date(since) {
+                Ok(since) => to_http_time(last_modified) <= since,
+                Err(_) => false,
+            },
+            _ => false,
+        }
+    }
+}
+
+// Capture http If-None-Match & If-Modified-Since headers of a read
+//
+pub fn capture_read_conditions(
+) -> impl Filter<Extract = (ReadConditions,), Error = warp::Rejection> + Clone {
+    warp::header::optional(IF_NONE_MATCH.as_str())
+        .and(warp::header::optional(IF_MODIFIED_SINCE.as_str()))
+        .map(|if_none_match, if_modified_since| ReadConditions { if_none_match, if_modified_since })
+}
+
+// Capture http If-Match header, listing the entity tags of the record versions a write requires
+// the record to still be at
+//
+pub fn capture_if_match_header(
+) -> impl Filter<Extract = (Option<String>,), Error = warp::Rejection> + Clone {
+    warp::header::optional(IF_MATCH.as_str())
+}
+
+// Parse the value of an If-Match header, being '*' or a comma separated list of entity tags,
+// returning the versions of the record the request requires the record to still be at, or nothing
+// if the record may be at any version, where weak or unrecognised entity tags never match a version
+//
+pub fn if_match_versions(if_match: Option<&str>) -> Option<Vec<i64>> {
+    let if_match = if_match.map(str::trim).filter(|if_match| *if_match != ANY_ETAG)?;
+    Some(
+        if_match
+            .split(',')
+            .filter_map(|tag| tag.trim().strip_prefix('"')?.strip_suffix('"')?.parse().ok())
+            .collect(),
+    )
+}
+
+// Build the response of a read, replacing it with an empty 304 Not Modified response if the client
+// already has the version, and labelling either response with the version's entity tag & time of
+// last modification, if the record or listing exists & they're known
+//
+pub fn versioned_reply(
+    reply: impl Reply, version: Option<&Version>, conditions: &ReadConditions,
+) -> Response {
+    let version = match version {
+        Some(version) => version,
+        None => return reply.into_response(),
+    };
+    let mut response = if conditions.is_current(version) {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        reply.into_response()
+    };
+
+    if let Some(Ok(value)) = version.etag.as_deref().map(HeaderValue::from_str) {
+        response.headers_mut().insert(ETAG, value);
+    }
+
+    if let Some(last_modified) = version.last_modified {
+        let value = httpdate::fmt_http_date(to_http_time(last_modified));
+
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            response.headers_mut().insert(LAST_MODIFIED, value);
+        }
+    }
+
+    response
+}
+
+// Convert a record's time of last modification to the precision of an http date, being seconds
+//
+fn to_http_time(time: DateTime) -> std::time::SystemTime {
+    let secs = time.timestamp_millis().div_euclid(1000);
+    let secs = u64::try_from(secs).unwrap_or_default();
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
@@ -0,0 +1,734 @@
+use mongodb::options::{
+    Acknowledgment, ClientOptions, ReadConcern, ReadPreference, ReadPreferenceOptions, WriteConcern,
+};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::Path;
+use std::time::Duration;
+use toml::Value;
+
+use crate::app2::DupScorePolicy;
+use crate::store::OperationSettings;
+
+const MAX_DB_NAME_LEN: usize = 63;
+const INVALID_DB_NAME_CHARS: &[char] = &['/', '\\', '.', ' ', '"', '$'];
+const SYSTEM_COLL_PREFIX: &str = "system.";
+const MAX_APP_NAME_LEN: usize = 128;
+const KNOWN_COMPRESSORS: &[&str] = &["zstd", "zlib", "snappy"];
+const COMPRESSORS_URL_OPTION: &str = "compressors=";
+const DRIVER_DEFAULT: &str = "default";
+const LINEARIZABLE: &str = "linearizable";
+const ENV_VAR_PREFIX: &str = "FLUIDITY_";
+
+// Settings of both applications, being the defaults, overridden by any provided in a TOML
+// configuration file, in turn overridden by any environment variable named after a setting's table
+// & key (eg. 'FLUIDITY_APP1_PORT' for the 'port' key of the '[app1]' table)
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub database: DatabaseConfig,
+    pub app1: App1Config,
+    pub app2: App2Config,
+}
+
+// Settings locating the shared book records and tuning the MongoDB client, where each client
+// setting only applies if the MongoDB URL doesn't specify the equivalent option, and a concern of
+// 'default' leaves it to the deployment's default
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub name: String,
+    pub collection: String,
+    pub connect_timeout_ms: u64,
+    pub server_selection_timeout_ms: u64,
+    pub max_pool_size: u32,
+    pub min_pool_size: u32,
+    pub compressors: Vec<String>,
+    pub read_preference: String,
+    pub read_concern: String,
+    pub write_concern: String,
+    pub write_concern_journal: bool,
+    pub write_concern_timeout_ms: u64,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            name: String::from("library"),
+            collection: String::from("books"),
+            connect_timeout_ms: 10_000,
+            server_selection_timeout_ms: 30_000,
+            max_pool_size: 10,
+            min_pool_size: 0,
+            compressors: Vec::new(),
+            read_preference: String::from("primary"),
+            read_concern: String::from(DRIVER_DEFAULT),
+            write_concern: String::from(DRIVER_DEFAULT),
+            write_concern_journal: false,
+            write_concern_timeout_ms: 0,
+        }
+    }
+}
+
+impl DatabaseConfig {
+    // Build the MongoDB client options from the URL, filling in any options the URL doesn't
+    // specify from these settings, returning an error if the URL or a setting is not valid
+    //
+    pub async fn client_options(
+        &self, url: &str,
+    ) -> Result<ClientOptions, Box<dyn Error + Send + Sync>> {
+        let mut client_options = ClientOptions::parse(url_with_compressors(url, &self.compressors))
+            .await
+            .map_err(|e| format!("The MongoDB URL is not valid: {}", e))?;
+
+        client_options
+            .connect_timeout
+            .get_or_insert(Duration::from_millis(self.connect_timeout_ms));
+        client_options
+            .server_selection_timeout
+            .get_or_insert(Duration::from_millis(self.server_selection_timeout_ms));
+        client_options.max_pool_size.get_or_insert(self.max_pool_size);
+        client_options.min_pool_size.get_or_insert(self.min_pool_size);
+
+        if client_options.selection_criteria.is_none() {
+            client_options.selection_criteria = self.read_preference()?.map(Into::into);
+        }
+
+        if client_options.read_concern.is_none() {
+            client_options.read_concern =
+                read_concern("database.read_concern", &self.read_concern)?;
+        }
+
+        if client_options.write_concern.is_none() {
+            client_options.write_concern = write_concern(
+                "database",
+                &self.write_concern,
+                self.write_concern_journal,
+                self.write_concern_timeout_ms,
+            )?;
+        }
+
+        Ok(client_options)
+    }
+
+    // Read preference mode, as named in a MongoDB URL (eg. 'secondaryPreferred')
+    //
+    fn read_preference(&self) -> Result<Option<ReadPreference>, Box<dyn Error + Send + Sync>> {
+        let options = ReadPreferenceOptions::default();
+        Ok(Some(match self.read_preference.as_str() {
+            DRIVER_DEFAULT => return Ok(None),
+            "primary" => ReadPreference::Primary,
+            "primaryPreferred" => ReadPreference::PrimaryPreferred { options },
+            "secondary" => ReadPreference::Secondary { options },
+            "secondaryPreferred" => ReadPreference::SecondaryPreferred { options },
+            "nearest" => ReadPreference::Nearest { options },
+            other => {
+                return Err(format!(
+                    "Setting 'database.read_preference' must be one of 'primary', \
+                    'primaryPreferred', 'secondary', 'secondaryPreferred' or 'nearest', not '{}'",
+                    other
+                )
+                .into())
+            }
+        }))
+    }
+}
+
+// Settings of app1's REST API server, including whether it creates its indexes on startup, the
+// name identifying its MongoDB client in the server's logs and the concerns & causal consistency of
+// its reads of a single book, its listings & its inventory changes (where a concern of 'default'
+// leaves it to the MongoDB client's)
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct App1Config {
+    pub app_name: String,
+    pub listen_address: IpAddr,
+    pub port: u16,
+    pub payload_limit: u64,
+    pub ensure_indexes: bool,
+    pub book_read_concern: String,
+    pub listing_read_concern: String,
+    pub write_concern: String,
+    pub write_concern_journal: bool,
+    pub write_concern_timeout_ms: u64,
+    pub causal_consistency: bool,
+}
+
+impl Default for App1Config {
+    fn default() -> Self {
+        Self {
+            app_name: String::from("fluidity-demo-app1"),
+            listen_address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: 8181,
+            payload_limit: 1024 * 16,
+            ensure_indexes: true,
+            book_read_concern: String::from(DRIVER_DEFAULT),
+            listing_read_concern: String::from(DRIVER_DEFAULT),
+            write_concern: String::from(DRIVER_DEFAULT),
+            write_concern_journal: false,
+            write_concern_timeout_ms: 0,
+            causal_consistency: true,
+        }
+    }
+}
+
+impl App1Config {
+    // Settings of app1's operations, returning an error if any are not valid
+    //
+    pub fn operation_settings(&self) -> Result<OperationSettings, Box<dyn Error + Send + Sync>> {
+        operation_settings(
+            "app1",
+            &self.book_read_concern,
+            &self.listing_read_concern,
+            &self.write_concern,
+            self.write_concern_journal,
+            self.write_concern_timeout_ms,
+            self.causal_consistency,
+        )
+    }
+}
+
+// Settings of app2's REST API server, including how it handles duplicate review scores, the scale
+// review scores must conform to, the name identifying its MongoDB client in the server's logs and
+// the concerns & causal consistency of its reads of a single book, its summaries & its score
+// changes (where a concern of 'default' leaves it to the MongoDB client's)
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct App2Config {
+    pub app_name: String,
+    pub listen_address: IpAddr,
+    pub port: u16,
+    pub payload_limit: u64,
+    pub duplicate_score_policy: DupScorePolicy,
+    pub score_min: f64,
+    pub score_max: f64,
+    pub score_step: f64,
+    pub book_read_concern: String,
+    pub listing_read_concern: String,
+    pub write_concern: String,
+    pub write_concern_journal: bool,
+    pub write_concern_timeout_ms: u64,
+    pub causal_consistency: bool,
+}
+
+impl Default for App2Config {
+    fn default() -> Self {
+        Self {
+            app_name: String::from("fluidity-demo-app2"),
+            listen_address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: 8282,
+            payload_limit: 1024 * 16,
+            duplicate_score_policy: DupScorePolicy::Reject,
+            score_min: 0.0,
+            score_max: 10.0,
+            score_step: 0.5,
+            book_read_concern: String::from(DRIVER_DEFAULT),
+            listing_read_concern: String::from(DRIVER_DEFAULT),
+            write_concern: String::from(DRIVER_DEFAULT),
+            write_concern_journal: false,
+            write_concern_timeout_ms: 0,
+            causal_consistency: true,
+        }
+    }
+}
+
+impl App2Config {
+    // Settings of app2's operations, returning an error if any are not valid
+    //
+    pub fn operation_settings(&self) -> Result<OperationSettings, Box<dyn Error + Send + Sync>> {
+        operation_settings(
+            "app2",
+            &self.book_read_concern,
+            &self.listing_read_concern,
+            &self.write_concern,
+            self.write_concern_journal,
+            self.write_concern_timeout_ms,
+            self.causal_consistency,
+        )
+    }
+}
+
+impl Config {
+    // Load settings from the configuration file, if any, and then the environment, returning an
+    // error if the file can't be read or any setting is unknown or of the wrong type
+    //
+    pub fn load(path: Option<&Path>) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let settings = match path {
+            Some(path) => {
+                let text = fs::read_to_string(path).map_err(|e| {
+                    format!("Unable to read the configuration file '{}': {}", path.display(), e)
+                })?;
+                text.parse::<Value>().map_err(|e| {
+                    format!("Configuration file '{}' is not valid TOML: {}", path.display(), e)
+                })?
+            }
+            None => Value::Table(toml::value::Table::new()),
+        };
+        Self::from_settings(settings, |var_name| env::var(var_name).ok())
+    }
+
+    // Build the configuration from the file's settings overridden by any environment variable
+    // found by the lookup, returning an error if any setting is unknown or of the wrong type
+    //
+    fn from_settings(
+        mut settings: Value, env_var: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        apply_env_overrides(&mut settings, env_var)?;
+        settings.try_into().map_err(|e| format!("Invalid configuration: {}", e).into())
+    }
+
+    // Check every setting has a usable value, returning an error describing the first which does
+    // not
+    //
+    pub fn validate(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let database = &self.database;
+
+        if database.name.is_empty()
+            || database.name.len() > MAX_DB_NAME_LEN
+            || database.name.contains(INVALID_DB_NAME_CHARS)
+        {
+            return Err(format!(
+                "Database name '{}' must be 1 to {} characters, none of which are any of: {}",
+                database.name,
+                MAX_DB_NAME_LEN,
+                INVALID_DB_NAME_CHARS.iter().collect::<String>()
+            )
+            .into());
+        }
+
+        if database.collection.is_empty()
+            || database.collection.contains('$')
+            || database.collection.starts_with(SYSTEM_COLL_PREFIX)
+        {
+            return Err(format!(
+                "Collection name '{}' must not be empty, contain '$' or start with '{}'",
+                database.collection, SYSTEM_COLL_PREFIX
+            )
+            .into());
+        }
+
+        check_positive("database.connect_timeout_ms", database.connect_timeout_ms)?;
+        check_positive(
+            "database.server_selection_timeout_ms",
+            database.server_selection_timeout_ms,
+        )?;
+        check_positive("database.max_pool_size", database.max_pool_size.into())?;
+
+        if database.min_pool_size > database.max_pool_size {
+            return Err(format!(
+                "Setting 'database.min_pool_size' ({}) must not exceed 'database.max_pool_size' \
+                ({})",
+                database.min_pool_size, database.max_pool_size
+            )
+            .into());
+        }
+
+        if let Some(compressor) =
+            database.compressors.iter().find(|name| !KNOWN_COMPRESSORS.contains(&name.as_str()))
+        {
+            return Err(format!(
+                "Setting 'database.compressors' must only list any of '{}', not '{}'",
+                KNOWN_COMPRESSORS.join("', '"),
+                compressor
+            )
+            .into());
+        }
+
+        database.read_preference()?;
+        read_concern("database.read_concern", &database.read_concern)?;
+
+        if database.read_concern == LINEARIZABLE {
+            return Err(format!(
+                "Setting 'database.read_concern' can't be '{}', as it only applies to reads of a \
+                single book, so set 'app1.book_read_concern' and/or 'app2.book_read_concern' \
+                instead",
+                LINEARIZABLE
+            )
+            .into());
+        }
+
+        write_concern(
+            "database",
+            &database.write_concern,
+            database.write_concern_journal,
+            database.write_concern_timeout_ms,
+        )?;
+        self.app1.operation_settings()?;
+        self.app2.operation_settings()?;
+        check_app_name("app1.app_name", &self.app1.app_name)?;
+        check_app_name("app2.app_name", &self.app2.app_name)?;
+        check_positive("app1.port", self.app1.port.into())?;
+        check_positive("app1.payload_limit", self.app1.payload_limit)?;
+        check_positive("app2.port", self.app2.port.into())?;
+        check_positive("app2.payload_limit", self.app2.payload_limit)?;
+        let app2 = &self.app2;
+
+        if !app2.score_min.is_finite()
+            || !app2.score_max.is_finite()
+            || app2.score_min >= app2.score_max
+        {
+            return Err(format!(
+                "Review score minimum ({}) must be less than the maximum ({})",
+                app2.score_min, app2.score_max
+            )
+            .into());
+        }
+
+        if !(0.0..=app2.score_max - app2.score_min).contains(&app2.score_step) {
+            return Err(format!(
+                "Review score step ({}) must be between 0 and the size of the range ({})",
+                app2.score_step,
+                app2.score_max - app2.score_min
+            )
+            .into());
+        }
+
+        Ok(())
+    }
+
+    // Check both apps can run together in one process, returning an error if they would both
+    // listen on the same address & port
+    //
+    pub fn validate_together(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if (self.app1.listen_address, self.app1.port) == (self.app2.listen_address, self.app2.port)
+        {
+            return Err(format!(
+                "App1 & app2 can't both listen on {}:{} when run together",
+                self.app1.listen_address, self.app1.port
+            )
+            .into());
+        }
+
+        Ok(())
+    }
+}
+
+// Override settings with any environment variable named after a setting's table & key (with the
+// 'FLUIDITY_' prefix), parsing the variable's value as the same type as the setting's default value
+//
+fn apply_env_overrides(
+    settings: &mut Value, env_var: impl Fn(&str) -> Option<String>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let defaults = Value::try_from(Config::default()).map_err(|e| e.to_string())?;
+    let defaults = defaults.as_table().ok_or("Default configuration is not a table")?;
+    let settings =
+        settings.as_table_mut().ok_or("Configuration must be a table of settings tables")?;
+
+    for (table_name, default_table) in defaults {
+        let default_table = default_table.as_table().ok_or("Default settings are not a table")?;
+
+        for (key, default) in default_table {
+            let var_name = format!("{}{}_{}", ENV_VAR_PREFIX, table_name, key).to_uppercase();
+
+            if let Some(text) = env_var(&var_name) {
+                let value = env_value(&var_name, &text, default)?;
+                let table = settings
+                    .entry(table_name.clone())
+                    .or_insert_with(|| Value::Table(toml::value::Table::new()));
+                let table = table
+                    .as_table_mut()
+                    .ok_or_else(|| format!("Setting '{}' must be a table", table_name))?;
+                table.insert(key.clone(), value);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Parse an environment variable's value as the same type as a setting's default value, returning
+// an error naming the variable if the value is not of that type
+//
+fn env_value(
+    var_name: &str, text: &str, default: &Value,
+) -> Result<Value, Box<dyn Error + Send + Sync>> {
+    let (value, expected) = match default {
+        Value::Integer(_) => (text.parse().ok().map(Value::Integer), "an integer"),
+        Value::Float(_) => {
+            (text.parse::<f64>().ok().filter(|num| num.is_finite()).map(Value::Float), "a number")
+        }
+        Value::Boolean(_) => (text.parse().ok().map(Value::Boolean), "'true' or 'false'"),
+        Value::Array(_) => (
+            Some(Value::Array(
+                text.split(',')
+                    .map(str::trim)
+                    .filter(|item| !item.is_empty())
+                    .map(|item| Value::String(item.to_string()))
+                    .collect(),
+            )),
+            "a comma separated list",
+        ),
+        _ => (Some(Value::String(text.to_string())), "text"),
+    };
+    value.ok_or_else(|| {
+        format!("Environment variable '{}' must be {}, not '{}'", var_name, expected, text).into()
+    })
+}
+
+// Read concern level (eg. 'majority') of the named setting
+//
+fn read_concern(
+    setting: &str, level: &str,
+) -> Result<Option<ReadConcern>, Box<dyn Error + Send + Sync>> {
+    Ok(Some(match level {
+        DRIVER_DEFAULT => return Ok(None),
+        "local" => ReadConcern::local(),
+        "available" => ReadConcern::available(),
+        "majority" => ReadConcern::majority(),
+        LINEARIZABLE => ReadConcern::linearizable(),
+        "snapshot" => ReadConcern::snapshot(),
+        other => {
+            return Err(format!(
+                "Setting '{}' must be one of '{}', 'local', 'available', 'majority', \
+                'linearizable' or 'snapshot', not '{}'",
+                setting, DRIVER_DEFAULT, other
+            )
+            .into())
+        }
+    }))
+}
+
+// Write concern of the settings in the table, acknowledged by the number of nodes, 'majority' or a
+// custom write concern name, optionally also requiring the write to be journaled and/or
+// acknowledged within a time limit, where an unacknowledged write concern (0 nodes) is rejected as
+// the MongoDB driver refuses unacknowledged writes, as well as any write in an explicit session
+//
+fn write_concern(
+    table: &str, w: &str, journal: bool, timeout_ms: u64,
+) -> Result<Option<WriteConcern>, Box<dyn Error + Send + Sync>> {
+    let w = match w {
+        DRIVER_DEFAULT => None,
+        "" => return Err(format!("Setting '{}.write_concern' must not be empty", table).into()),
+        w => Some(match w.parse::<u32>() {
+            Ok(nodes) => Acknowledgment::Nodes(nodes),
+            Err(_) => Acknowledgment::from(w.to_string()),
+        }),
+    };
+
+    if w == Some(Acknowledgment::Nodes(0)) {
+        return Err(format!(
+            "Setting '{}.write_concern' can't be 0, as writes must be acknowledged",
+            table
+        )
+        .into());
+    }
+
+    let mut write_concern = WriteConcern::default();
+    write_concern.w = w;
+    write_concern.journal = journal.then_some(true);
+    write_concern.w_timeout = (timeout_ms > 0).then(|| Duration::from_millis(timeout_ms));
+    Ok((write_concern != WriteConcern::default()).then_some(write_concern))
+}
+
+// Settings of an app's operations, where the read concern 'linearizable' is rejected for listings,
+// as MongoDB only guarantees linearizable reads of a single document
+//
+fn operation_settings(
+    table: &str, book_read_concern: &str, listing_read_concern: &str, write_concern_w: &str,
+    write_concern_journal: bool, write_concern_timeout_ms: u64, causal_consistency: bool,
+) -> Result<OperationSettings, Box<dyn Error + Send + Sync>> {
+    if listing_read_concern == LINEARIZABLE {
+        return Err(format!(
+            "Setting '{}.listing_read_concern' can't be '{}', as it only applies to reads of a \
+            single book",
+            table, LINEARIZABLE
+        )
+        .into());
+    }
+
+    Ok(OperationSettings {
+        book_read_concern: read_concern(
+            &format!("{}.book_read_concern", table),
+            book_read_concern,
+        )?,
+        listing_read_concern: read_concern(
+            &format!("{}.listing_read_concern", table),
+            listing_read_concern,
+        )?,
+        write_concern: write_concern(
+            table,
+            write_concern_w,
+            write_concern_journal,
+            write_concern_timeout_ms,
+        )?,
+        causal_consistency,
+    })
+}
+
+// Add the compressors to the options of the MongoDB URL, as the driver only accepts compressors
+// via the URL, unless there are none or the URL already lists its own compressors
+//
+fn url_with_compressors(url: &str, compressors: &[String]) -> String {
+    if compressors.is_empty() || url.to_lowercase().contains(COMPRESSORS_URL_OPTION) {
+        return url.to_string();
+    }
+
+    let hosts_and_options = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let separator = if url.ends_with(['?', '&']) {
+        ""
+    } else if hosts_and_options.contains('?') {
+        "&"
+    } else if hosts_and_options.contains('/') {
+        "?"
+    } else {
+        "/?"
+    };
+    format!("{}{}{}{}", url, separator, COMPRESSORS_URL_OPTION, compressors.join(","))
+}
+
+// Check a client app name is not empty and not longer than the MongoDB server accepts
+//
+fn check_app_name(name: &str, value: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if value.is_empty() || value.len() > MAX_APP_NAME_LEN {
+        return Err(
+            format!("Setting '{}' must be 1 to {} bytes long", name, MAX_APP_NAME_LEN).into()
+        );
+    }
+
+    Ok(())
+}
+
+// Check a numeric setting is greater than zero, returning an error naming the setting if not
+//
+fn check_positive(name: &str, value: u64) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if value == 0 {
+        return Err(format!("Setting '{}' must be greater than 0", name).into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::Cli;
+    use clap::Parser;
+    use std::collections::HashMap;
+
+    // Configuration from the TOML settings overridden by the environment variables
+    //
+    fn config_from(toml: &str, env_vars: &[(&str, &str)]) -> Config {
+        let env_vars: HashMap<_, _> = env_vars.iter().copied().collect();
+        Config::from_settings(toml.parse().unwrap(), |name| {
+            env_vars.get(name).map(|value| value.to_string())
+        })
+        .unwrap()
+    }
+
+    // Error describing why the configuration, after applying the change, is invalid
+    //
+    fn validation_error(change: impl FnOnce(&mut Config)) -> String {
+        let mut config = Config::default();
+        change(&mut config);
+        config.validate().unwrap_err().to_string()
+    }
+
+    #[test]
+    fn env_overrides_file_and_cli_overrides_both() {
+        let toml = "[app1]\nport = 9000\npayload_limit = 1000\n\n[database]\nname = \"file\"\n";
+        let env_vars = [("FLUIDITY_APP1_PORT", "9100"), ("FLUIDITY_DATABASE_NAME", "env")];
+        let mut config = config_from(toml, &env_vars);
+        assert_eq!(config.app1.payload_limit, 1000);
+        assert_eq!((config.app1.port, config.database.name.as_str()), (9100, "env"));
+
+        let cli = Cli::try_parse_from(["fluidity", "app1", "memory", "--port", "9200"]).unwrap();
+        cli.command.override_config(&mut config);
+        assert_eq!((config.app1.port, config.database.name.as_str()), (9200, "env"));
+        assert_eq!(config.app1.payload_limit, 1000);
+    }
+
+    #[test]
+    fn env_vars_need_prefix_and_matching_type() {
+        let config = config_from("", &[("APP1_PORT", "9100"), ("FLUIDITY_APP2_SCORE_MAX", "5")]);
+        assert_eq!(config.app1.port, App1Config::default().port);
+        assert_eq!(config.app2.score_max, 5.0);
+        let compressors = config_from("", &[("FLUIDITY_DATABASE_COMPRESSORS", "zstd, snappy,")]);
+        assert_eq!(compressors.database.compressors, ["zstd", "snappy"]);
+
+        let err = Config::from_settings(Value::Table(toml::value::Table::new()), |name| {
+            (name == "FLUIDITY_APP1_PORT").then(|| String::from("high"))
+        })
+        .unwrap_err();
+        assert!(err.to_string().contains("'FLUIDITY_APP1_PORT' must be an integer"));
+    }
+
+    #[test]
+    fn unknown_settings_rejected() {
+        let settings = "[app1]\nprot = 9000\n".parse().unwrap();
+        assert!(Config::from_settings(settings, |_| None).is_err());
+    }
+
+    #[test]
+    fn defaults_valid() {
+        assert!(Config::default().validate().is_ok());
+        assert!(Config::default().validate_together().is_ok());
+    }
+
+    #[test]
+    fn invalid_settings_rejected() {
+        assert!(validation_error(|config| config.app1.port = 0).contains("'app1.port'"));
+        assert!(validation_error(|config| config.app2.port = 0).contains("'app2.port'"));
+        let err = validation_error(|config| config.app2.score_min = config.app2.score_max + 1.0);
+        assert!(err.contains("must be less than the maximum"));
+        let err = validation_error(|config| config.app2.score_min = config.app2.score_max);
+        assert!(err.contains("must be less than the maximum"));
+        let err = validation_error(|config| config.app2.score_step = -0.5);
+        assert!(err.contains("step (-0.5)"));
+        let err = validation_error(|config| config.app2.score_step = 100.0);
+        assert!(err.contains("step (100)"));
+        let err = validation_error(|config| config.database.read_concern = "strong".into());
+        assert!(err.contains("'database.read_concern'"));
+        let err = validation_error(|config| config.app1.book_read_concern = "strong".into());
+        assert!(err.contains("'app1.book_read_concern'"));
+        let err = validation_error(|config| config.database.read_preference = "any".into());
+        assert!(err.contains("'database.read_preference'"));
+        let err = validation_error(|config| config.database.write_concern = "0".into());
+        assert!(err.contains("'database.write_concern' can't be 0"));
+        let err = validation_error(|config| config.app2.write_concern = "0".into());
+        assert!(err.contains("'app2.write_concern' can't be 0"));
+        let err = validation_error(|config| config.database.compressors = vec!["lz4".into()]);
+        assert!(err.contains("not 'lz4'"));
+    }
+
+    #[test]
+    fn linearizable_only_for_single_book_reads() {
+        let err = validation_error(|config| config.database.read_concern = LINEARIZABLE.into());
+        assert!(err.contains("'database.read_concern' can't be"));
+        let err = validation_error(|config| config.app1.listing_read_concern = LINEARIZABLE.into());
+        assert!(err.contains("'app1.listing_read_concern' can't be"));
+        let mut config = Config::default();
+        config.app2.book_read_concern = LINEARIZABLE.into();
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn apps_together_need_distinct_ports() {
+        let mut config = Config::default();
+        config.app2.port = config.app1.port;
+        assert!(config.validate_together().is_err());
+    }
+
+    #[test]
+    fn compressors_added_to_url_options() {
+        let compressors = [String::from("zstd"), String::from("snappy")];
+        let cases = [
+            ("mongodb://localhost", "mongodb://localhost/?compressors=zstd,snappy"),
+            ("mongodb://localhost/", "mongodb://localhost/?compressors=zstd,snappy"),
+            ("mongodb://localhost/db", "mongodb://localhost/db?compressors=zstd,snappy"),
+            ("mongodb://h1,h2/?w=1", "mongodb://h1,h2/?w=1&compressors=zstd,snappy"),
+            ("mongodb://localhost/?", "mongodb://localhost/?compressors=zstd,snappy"),
+            ("mongodb://localhost/?w=1&", "mongodb://localhost/?w=1&compressors=zstd,snappy"),
+            ("mongodb://localhost/?compressors=zlib", "mongodb://localhost/?compressors=zlib"),
+            ("mongodb://localhost/?Compressors=zlib", "mongodb://localhost/?Compressors=zlib"),
+        ];
+
+        for (url, expected) in cases {
+            assert_eq!(url_with_compressors(url, &compressors), expected);
+        }
+
+        assert_eq!(url_with_compressors("mongodb://localhost", &[]), "mongodb://localhost");
+    }
+}
@@ -0,0 +1,245 @@
+use serde::Serialize;
+use std::convert::Infallible;
+use std::fmt;
+use warp::{
+    body::BodyDeserializeError,
+    http::StatusCode,
+    reject::{InvalidQuery, MethodNotAllowed, PayloadTooLarge, Reject, UnsupportedMediaType},
+    Rejection, Reply,
+};
+
+const DUP_KEY_ERROR_CODE: i32 = 11000;
+const INDEX_NOT_FOUND_ERROR_CODE: i32 = 27;
+
+// Error raised by either app's database tier or REST API handlers
+#[derive(Debug)]
+pub enum AppError {
+    // A field required by the operation was not provided
+    MissingField(String),
+    // A field was provided but its value is unacceptable
+    InvalidField { field: String, reason: String },
+    // A write would have clashed with an existing record on a unique index
+    DuplicateKey { field: Option<String>, message: String },
+    // The record targeted by the operation does not exist
+    NotFound(String),
+    // The record targeted by the operation has changed since the version the client last read
+    PreconditionFailed(String),
+    // An index the operation requires (eg. the text index for searches) has not been created,
+    // which retrying won't resolve
+    MissingIndex(String),
+    // The database could not be reached or the driver failed to perform the operation
+    Database(mongodb::error::Error),
+    // Any other unexpected failure, such as an inability to convert a record to/from BSON
+    Internal(String),
+}
+
+// JSON body returned to REST API clients when a request fails
+#[derive(Debug, Serialize)]
+struct ErrorPayload {
+    code: &'static str,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    field: Option<String>,
+}
+
+impl AppError {
+    // HTTP status code to respond with for this error
+    //
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::MissingField(_) | Self::InvalidField { .. } => StatusCode::BAD_REQUEST,
+            Self::DuplicateKey { .. } => StatusCode::CONFLICT,
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::PreconditionFailed(_) => StatusCode::PRECONDITION_FAILED,
+            Self::Database(_) => StatusCode::SERVICE_UNAVAILABLE,
+            Self::MissingIndex(_) | Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    // Machine-readable code identifying the type of error
+    //
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::MissingField(_) => "missing_field",
+            Self::InvalidField { .. } => "invalid_field",
+            Self::DuplicateKey { .. } => "duplicate_key",
+            Self::NotFound(_) => "not_found",
+            Self::PreconditionFailed(_) => "precondition_failed",
+            Self::MissingIndex(_) => "missing_index",
+            Self::Database(_) => "database_unavailable",
+            Self::Internal(_) => "internal_error",
+        }
+    }
+
+    // Name of the field which caused the error, if any
+    //
+    pub fn field(&self) -> Option<&str> {
+        match self {
+            Self::MissingField(field) | Self::InvalidField { field, .. } => Some(field),
+            Self::DuplicateKey { field, .. } => field.as_deref(),
+            Self::NotFound(_)
+            | Self::PreconditionFailed(_)
+            | Self::MissingIndex(_)
+            | Self::Database(_)
+            | Self::Internal(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingField(field) => write!(f, "Field `{}` is empty, but is required", field),
+            Self::InvalidField { field, reason } => {
+                write!(f, "Field `{}` is invalid: {}", field, reason)
+            }
+            Self::DuplicateKey { message, .. } => write!(f, "Duplicate record: {}", message),
+            Self::NotFound(message) => write!(f, "Not found: {}", message),
+            Self::PreconditionFailed(message) => write!(f, "Precondition failed: {}", message),
+            Self::MissingIndex(message) => write!(
+                f,
+                "Missing database index, which can be created via 'admin ensure-indexes': {}",
+                message
+            ),
+            Self::Database(e) => write!(f, "Database error: {}", e),
+            Self::Internal(message) => write!(f, "Internal error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl Reject for AppError {}
+
+impl From<mongodb::error::Error> for AppError {
+    fn from(e: mongodb::error::Error) -> Self {
+        use mongodb::error::{ErrorKind, WriteFailure};
+
+        match e.kind.as_ref() {
+            ErrorKind::Command(command_err) if command_err.code == INDEX_NOT_FOUND_ERROR_CODE => {
+                Self::MissingIndex(command_err.message.clone())
+            }
+            ErrorKind::Write(WriteFailure::WriteError(write_err))
+                if write_err.code == DUP_KEY_ERROR_CODE =>
+            {
+                Self::DuplicateKey {
+                    field: dup_key_fields(&write_err.message),
+                    message: write_err.message.clone(),
+                }
+            }
+            _ => Self::Database(e),
+        }
+    }
+}
+
+impl From<bson::de::Error> for AppError {
+    fn from(e: bson::de::Error) -> Self {
+        Self::Internal(e.to_string())
+    }
+}
+
+impl From<bson::ser::Error> for AppError {
+    fn from(e: bson::ser::Error) -> Self {
+        Self::Internal(e.to_string())
+    }
+}
+
+// Convert any request rejection into a JSON error response with an appropriate HTTP status code
+//
+pub async fn handle_rejection(err: Rejection) -> Result<impl Reply, Infallible> {
+    let (status, payload) = if let Some(app_err) = err.find::<AppError>() {
+        (
+            app_err.status(),
+            ErrorPayload {
+                code: app_err.code(),
+                message: app_err.to_string(),
+                field: app_err.field().map(String::from),
+            },
+        )
+    } else if err.is_not_found() {
+        (StatusCode::NOT_FOUND, error_payload("not_found", "Resource not found"))
+    } else if let Some(e) = err.find::<BodyDeserializeError>() {
+        (StatusCode::BAD_REQUEST, error_payload("invalid_body", &e.to_string()))
+    } else if let Some(e) = err.find::<InvalidQuery>() {
+        (StatusCode::BAD_REQUEST, error_payload("invalid_query", &e.to_string()))
+    } else if let Some(e) = err.find::<PayloadTooLarge>() {
+        (StatusCode::PAYLOAD_TOO_LARGE, error_payload("payload_too_large", &e.to_string()))
+    } else if let Some(e) = err.find::<UnsupportedMediaType>() {
+        (
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            error_payload("unsupported_media_type", &e.to_string()),
+        )
+    } else if let Some(e) = err.find::<MethodNotAllowed>() {
+        (StatusCode::METHOD_NOT_ALLOWED, error_payload("method_not_allowed", &e.to_string()))
+    } else {
+        eprintln!("Unhandled request rejection: {:?}", err);
+        (StatusCode::INTERNAL_SERVER_ERROR, error_payload("internal_error", "Unexpected error"))
+    };
+
+    Ok(warp::reply::with_status(warp::reply::json(&payload), status))
+}
+
+// Build an error payload which is not related to a specific field
+//
+fn error_payload(code: &'static str, message: &str) -> ErrorPayload {
+    ErrorPayload { code, message: message.to_string(), field: None }
+}
+
+// Extract the names of the fields covered by the unique index named in a duplicate key error
+// message (eg. 'index: title_1_author_1 dup key' gives 'title,author')
+//
+fn dup_key_fields(message: &str) -> Option<String> {
+    let index_name = message.split("index: ").nth(1)?.split_whitespace().next()?;
+    let fields: Vec<&str> = index_name.split('_').step_by(2).collect();
+    Some(fields.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bson::doc;
+    use mongodb::error::{CommandError, ErrorKind};
+
+    // Driver error for a command which failed with the code
+    //
+    fn command_error(code: i32, code_name: &str, message: &str) -> mongodb::error::Error {
+        let command_err: CommandError =
+            bson::from_document(doc! {"code": code, "codeName": code_name, "errmsg": message})
+                .unwrap();
+        ErrorKind::Command(command_err).into()
+    }
+
+    #[test]
+    fn dup_key_fields_named_by_index() {
+        let message = "E11000 duplicate key error collection: library.books index: \
+                       title_1_author_1 dup key: { title: \"Dune\", author: \"Frank Herbert\" }";
+        assert_eq!(dup_key_fields(message).as_deref(), Some("title,author"));
+        assert_eq!(dup_key_fields("E11000 duplicate key error").as_deref(), None);
+    }
+
+    #[test]
+    fn missing_field_named_in_bad_request() {
+        let err = AppError::MissingField(String::from("title"));
+        assert_eq!(err.status(), StatusCode::BAD_REQUEST);
+        assert_eq!((err.code(), err.field()), ("missing_field", Some("title")));
+    }
+
+    #[test]
+    fn missing_index_not_retryable() {
+        let err = AppError::from(command_error(
+            INDEX_NOT_FOUND_ERROR_CODE,
+            "IndexNotFound",
+            "text index required for $text query",
+        ));
+        assert_eq!(err.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(err.code(), "missing_index");
+        assert!(err.to_string().contains("text index required"));
+    }
+
+    #[test]
+    fn other_command_failures_retryable() {
+        let err = AppError::from(command_error(91, "ShutdownInProgress", "shutting down"));
+        assert_eq!(err.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(err.code(), "database_unavailable");
+    }
+}
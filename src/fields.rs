@@ -0,0 +1,153 @@
+use bson::{doc, Document};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::error::AppError;
+
+// Payload key of a book's id, which is always kept so a narrowed book can still be addressed
+const ID_KEY: &str = "id";
+
+// Subset of an app's owned fields of the shared book records, to project when reading records and
+// to include in responses
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldSelection {
+    fields: Vec<&'static str>,
+}
+
+impl FieldSelection {
+    // Select all the app's owned fields
+    //
+    pub fn all(owned: &[&'static str]) -> Self {
+        Self { fields: owned.to_vec() }
+    }
+
+    // Parse the value of a 'fields' query parameter, being a comma separated list of field names
+    // (eg. 'title,author'), returning an error if any field is not one of the app's owned fields
+    //
+    pub fn parse(fields: &str, owned: &[&'static str]) -> Result<Self, AppError> {
+        let mut selection = Self { fields: vec![] };
+
+        for name in fields.split(',') {
+            let field = owned.iter().find(|field| **field == name).ok_or_else(|| {
+                AppError::InvalidField {
+                    field: String::from("fields"),
+                    reason: format!("`{}` is not one of the fields: {}", name, owned.join(", ")),
+                }
+            })?;
+            selection.add(field);
+        }
+
+        Ok(selection)
+    }
+
+    // Add a field to the selection, if not already selected
+    //
+    pub fn add(&mut self, field: &'static str) {
+        if !self.contains(field) {
+            self.fields.push(field);
+        }
+    }
+
+    // Check whether a field is selected
+    //
+    pub fn contains(&self, field: &str) -> bool {
+        self.fields.contains(&field)
+    }
+
+    // Build the projection of the selected fields
+    //
+    pub fn projection(&self) -> Document {
+        let mut projection = doc! {};
+
+        for field in &self.fields {
+            projection.insert(*field, 1);
+        }
+
+        projection
+    }
+
+    // Convert a response payload (or list of payloads) to JSON, only keeping the keys of each
+    // payload which are selected fields
+    //
+    pub fn narrow<T>(&self, payload: &T) -> Result<Value, AppError>
+    where
+        T: Serialize,
+    {
+        self.narrow_derived(payload, |key| key)
+    }
+
+    // Convert a response payload (or list of payloads) to JSON, only keeping the keys of each
+    // payload which are derived from a selected field, where the source function names the field
+    // each key is derived from
+    //
+    pub fn narrow_derived<T>(
+        &self, payload: &T, source: fn(&str) -> &str,
+    ) -> Result<Value, AppError>
+    where
+        T: Serialize,
+    {
+        let mut json =
+            serde_json::to_value(payload).map_err(|e| AppError::Internal(e.to_string()))?;
+
+        match &mut json {
+            Value::Array(items) => {
+                for item in items {
+                    self.narrow_object(item, source);
+                }
+            }
+            object => self.narrow_object(object, source),
+        }
+
+        Ok(json)
+    }
+
+    // Remove the keys of a JSON object, except the id, which aren't derived from a selected field
+    //
+    fn narrow_object(&self, json: &mut Value, source: fn(&str) -> &str) {
+        if let Value::Object(object) = json {
+            *object = std::mem::take(object)
+                .into_iter()
+                .filter(|(key, _)| key == ID_KEY || self.contains(source(key)))
+                .collect();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const OWNED: &[&str] = &["title", "author", "year"];
+
+    #[test]
+    fn only_owned_fields_selected() {
+        let selection = FieldSelection::parse("year,title,year", OWNED).unwrap();
+        assert_eq!(selection.projection(), doc! {"year": 1, "title": 1});
+        assert!(!selection.contains("author"));
+
+        match FieldSelection::parse("title,quantity", OWNED) {
+            Err(AppError::InvalidField { field, reason }) => {
+                assert_eq!(field, "fields");
+                assert!(reason.contains("`quantity`"));
+            }
+            other => panic!("Expected invalid field error, not {:?}", other),
+        }
+    }
+
+    #[test]
+    fn payloads_narrowed_to_selected_keys() {
+        let selection = FieldSelection::parse("title", OWNED).unwrap();
+        let books = json!([{"title": "Dune", "year": 1965}, {"title": "Emma", "year": 1815}]);
+        assert_eq!(
+            selection.narrow(&books).unwrap(),
+            json!([{"title": "Dune"}, {"title": "Emma"}])
+        );
+        let book = json!({"title": "Dune", "score": 9.5, "scores_counted": 2});
+        let derived = selection.narrow_derived(&book, |key| match key {
+            "title" => key,
+            _ => "scores",
+        });
+        assert_eq!(derived.unwrap(), json!({"title": "Dune"}));
+    }
+}
@@ -1,57 +1,138 @@
-use std::env;
+use clap::Parser;
 use std::error::Error;
+use std::net::SocketAddr;
 use std::process::exit;
 
+mod admin;
+use admin::{admin_ensure_indexes, admin_ping};
+
 mod app1;
 use app1::app1_main;
 
 mod app2;
 use app2::app2_main;
 
-const APP1_ID: &str = "app1";
-const APP2_ID: &str = "app2";
+mod causal;
+
+mod cli;
+use cli::{AdminTask, Cli, Command, StoreArgs};
+
+mod conditional;
+
+mod config;
+use config::{Config, DatabaseConfig};
+
+mod error;
 
-// Main bootstrap function which starts app1 or app2 depending on the command line args passed in
+mod fields;
+
+mod mem_store;
+
+mod store;
+use store::StoreLocation;
+
+mod token;
+
+// Main bootstrap function which starts app1, app2 or both, or performs an admin task, depending on
+// the command line args passed in, exiting with an error if either fails (eg. can't listen)
 //
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
-    let (appid, url) = get_appid_and_url_args_or_exit();
-
-    match appid.as_str() {
-        APP1_ID => app1_main(&url).await?,
-        APP2_ID => app2_main(&url).await?,
-        _ => {
-            eprintln!(
-                "\nERROR: Application id parameter must have the value '{}' or '{}'\n",
-                APP1_ID, APP2_ID
-            );
-            exit(1);
+async fn main() {
+    let cli = Cli::parse();
+    let config = get_config_or_exit(&cli);
+
+    if let Err(e) = run_command(&cli, &config).await {
+        eprintln!("\nERROR: {}\n", e);
+        exit(1);
+    }
+}
+
+// Start app1, app2 or both, or perform an admin task, as the command line args specify
+//
+async fn run_command(cli: &Cli, config: &Config) -> Result<(), Box<dyn Error + Send + Sync>> {
+    match &cli.command {
+        Command::App1(args) => {
+            let location = get_store_location_or_exit(&args.store, &config.database).await;
+            let listen = SocketAddr::new(config.app1.listen_address, config.app1.port);
+            let connection = location.connect(&config.app1.app_name)?;
+            app1_main(&location, &connection, listen, &config.app1, shutdown_signal()).await?
+        }
+        Command::App2(args) => {
+            let location = get_store_location_or_exit(&args.store, &config.database).await;
+            let listen = SocketAddr::new(config.app2.listen_address, config.app2.port);
+            let connection = location.connect(&config.app2.app_name)?;
+            app2_main(&location, &connection, listen, &config.app2, shutdown_signal()).await?
+        }
+        Command::All(args) => {
+            // Both apps share the one connection, stopping together if either fails (including
+            // failing to listen) or on the interrupt signal
+            let location = get_store_location_or_exit(&args.store, &config.database).await;
+            let app_name = format!("{},{}", config.app1.app_name, config.app2.app_name);
+            let connection = location.connect(&app_name)?;
+            let app1_listen = SocketAddr::new(config.app1.listen_address, config.app1.port);
+            let app2_listen = SocketAddr::new(config.app2.listen_address, config.app2.port);
+
+            tokio::try_join!(
+                app1_main(&location, &connection, app1_listen, &config.app1, shutdown_signal()),
+                app2_main(&location, &connection, app2_listen, &config.app2, shutdown_signal())
+            )?;
+        }
+        Command::Admin(AdminTask::Ping(store)) => {
+            admin_ping(&get_store_location_or_exit(store, &config.database).await).await?
+        }
+        Command::Admin(AdminTask::EnsureIndexes(store)) => {
+            admin_ensure_indexes(&get_store_location_or_exit(store, &config.database).await).await?
         }
     }
 
     Ok(())
 }
 
-// Extract the Application ID + URL parameters passed on the command line or exit if not provided
+// Complete when the process receives the interrupt signal (Ctrl-C), so the apps stop gracefully
 //
-fn get_appid_and_url_args_or_exit() -> (String, String) {
-    let args: Vec<String> = env::args().collect();
-
-    if args.len() < 3 {
-        eprintln!(
-            "\nERROR: An application id ('app1' or 'app2') + the MongoDB URL both need to be \
-            provided as arguments\n"
-        );
-        exit(1);
+async fn shutdown_signal() {
+    match tokio::signal::ctrl_c().await {
+        Ok(()) => println!("Stopping on interrupt, after finishing requests in progress"),
+        Err(e) => {
+            eprintln!("Unable to listen for the interrupt signal, so can only be killed: {}", e);
+            std::future::pending().await
+        }
     }
+}
 
-    if !args[2].starts_with("mongodb") {
-        eprintln!(
-            "\nERROR: The second parameter (URL) must be a valid MongoDB URL starting with the \
-            text 'mongodb'\n"
-        );
-        exit(1);
+// Load the settings from the configuration file & environment, overridden by the command line args,
+// or exit if any setting can't be loaded or is not valid
+//
+fn get_config_or_exit(cli: &Cli) -> Config {
+    let config = Config::load(cli.config.as_deref()).and_then(|mut config| {
+        cli.command.override_config(&mut config);
+        config.validate()?;
+
+        if let Command::All(_) = cli.command {
+            config.validate_together()?;
+        }
+
+        Ok(config)
+    });
+
+    match config {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("\nERROR: {}\n", e);
+            exit(1);
+        }
     }
+}
 
-    (args[1].to_string(), args[2].to_string())
+// Locate the shared book records from the command line args, environment & configuration or exit
+// if the MongoDB URL is not provided or is not valid
+//
+async fn get_store_location_or_exit(store: &StoreArgs, database: &DatabaseConfig) -> StoreLocation {
+    match store.location(database).await {
+        Ok(location) => location,
+        Err(e) => {
+            eprintln!("\nERROR: {}\n", e);
+            exit(1);
+        }
+    }
 }
@@ -0,0 +1,255 @@
+use bson::{oid::ObjectId, Bson, Document};
+use std::cmp::Ordering;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::error::AppError;
+use crate::store::WriteCounts;
+
+// Pseudo URL which, when provided instead of a MongoDB URL, runs an app against an in-memory store
+pub const IN_MEMORY_URL: &str = "memory";
+
+// In-process stand-in for the 'library.books' collection, holding raw BSON documents (rather than
+// each app's typed Book record) so that different apps can share the same records whilst each
+// only reads & writes its own subset of fields, just like when running against MongoDB
+#[derive(Debug, Clone, Default)]
+pub struct MemCollection {
+    docs: Arc<Mutex<Vec<Document>>>,
+}
+
+// Emulates the basic collection operations used by the apps, including the unique index on the
+// fields '(title, author)' created by 'data/book-data-prep-for-app1.js'
+//
+impl MemCollection {
+    // Return copies of all documents matching the filter predicate, in insertion order
+    //
+    pub fn find<F>(&self, filter: F) -> Vec<Document>
+    where
+        F: Fn(&Document) -> bool,
+    {
+        self.lock().iter().filter(|doc| filter(doc)).cloned().collect()
+    }
+
+    // Return a copy of the first document matching the filter predicate
+    //
+    pub fn find_one<F>(&self, filter: F) -> Option<Document>
+    where
+        F: Fn(&Document) -> bool,
+    {
+        self.lock().iter().find(|doc| filter(doc)).cloned()
+    }
+
+    // Insert new document, generating an '_id' if missing and enforcing the unique index
+    //
+    pub fn insert_one(&self, mut doc: Document) -> Result<(), AppError> {
+        let mut docs = self.lock();
+
+        if docs.iter().any(|existing| same_title_and_author(existing, &doc)) {
+            return Err(dup_key_error(&doc));
+        }
+
+        if !doc.contains_key("_id") {
+            doc.insert("_id", ObjectId::new());
+        }
+
+        docs.push(doc);
+        Ok(())
+    }
+
+    // Apply the update function to the first document matching the filter predicate, where the
+    // update function reports whether it actually changed the document, enforcing the unique index
+    // if the update changes the document's title or author
+    //
+    pub fn update_one<F, U>(&self, filter: F, update: U) -> Result<WriteCounts, AppError>
+    where
+        F: Fn(&Document) -> bool,
+        U: FnOnce(&mut Document) -> Result<bool, AppError>,
+    {
+        let mut docs = self.lock();
+
+        match docs.iter().position(filter) {
+            Some(pos) => {
+                // Work on a copy so a failed update leaves the stored document untouched
+                let mut updated = docs[pos].clone();
+                let modified = update(&mut updated)?;
+
+                if !same_title_and_author(&docs[pos], &updated)
+                    && docs.iter().any(|existing| same_title_and_author(existing, &updated))
+                {
+                    return Err(dup_key_error(&updated));
+                }
+
+                docs[pos] = updated;
+                Ok(WriteCounts { matched_count: 1, modified_count: u64::from(modified) })
+            }
+            None => Ok(WriteCounts::default()),
+        }
+    }
+
+    // Remove the first document matching the filter predicate
+    //
+    pub fn delete_one<F>(&self, filter: F) -> WriteCounts
+    where
+        F: Fn(&Document) -> bool,
+    {
+        let mut docs = self.lock();
+
+        match docs.iter().position(filter) {
+            Some(pos) => {
+                docs.remove(pos);
+                WriteCounts { matched_count: 1, modified_count: 1 }
+            }
+            None => WriteCounts::default(),
+        }
+    }
+
+    // Obtain exclusive access to the documents, ignoring poisoning from a panicked holder
+    //
+    fn lock(&self) -> MutexGuard<'_, Vec<Document>> {
+        self.docs.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+// Check whether a document's string field has exactly the given value
+//
+pub fn field_equals(doc: &Document, field: &str, value: &str) -> bool {
+    matches!(doc.get(field), Some(Bson::String(val)) if val == value)
+}
+
+// Check whether any element of a document's array field satisfies the predicate, emulating a
+// MongoDB query filter on an array element's field (eg. '{"scores.reference": "..."}')
+//
+pub fn array_contains<F>(doc: &Document, field: &str, predicate: F) -> bool
+where
+    F: Fn(&Bson) -> bool,
+{
+    match doc.get(field) {
+        Some(Bson::Array(array)) => array.iter().any(predicate),
+        _ => false,
+    }
+}
+
+// Emulate MongoDB's '$inc' update operator for a 32-bit integer increment
+//
+pub fn inc_field(doc: &mut Document, field: &str, by: i32) -> Result<(), AppError> {
+    let incremented = match doc.get(field) {
+        None | Some(Bson::Null) => Bson::Int32(by),
+        Some(Bson::Int32(val)) => match val.checked_add(by) {
+            Some(sum) => Bson::Int32(sum),
+            None => Bson::Int64(i64::from(*val) + i64::from(by)),
+        },
+        Some(Bson::Int64(val)) => Bson::Int64(val + i64::from(by)),
+        Some(Bson::Double(val)) => Bson::Double(val + f64::from(by)),
+        Some(other) => {
+            return Err(AppError::Internal(format!(
+                "Cannot apply $inc to a value of non-numeric type: field `{}` is {:?}",
+                field,
+                other.element_type()
+            )))
+        }
+    };
+    doc.insert(field, incremented);
+    Ok(())
+}
+
+// Emulate MongoDB's '$push' update operator, creating the array field if not yet present
+//
+pub fn push_to_array(doc: &mut Document, field: &str, value: Bson) -> Result<(), AppError> {
+    match doc.get_mut(field) {
+        None => {
+            doc.insert(field, vec![value]);
+            Ok(())
+        }
+        Some(Bson::Array(array)) => {
+            array.push(value);
+            Ok(())
+        }
+        Some(other) => Err(AppError::Internal(format!(
+            "The field `{}` must be an array but is of type {:?}",
+            field,
+            other.element_type()
+        ))),
+    }
+}
+
+// Emulate MongoDB's '$pull' update operator, returning whether any array elements were removed
+//
+pub fn pull_from_array<F>(doc: &mut Document, field: &str, predicate: F) -> bool
+where
+    F: Fn(&Bson) -> bool,
+{
+    match doc.get_mut(field) {
+        Some(Bson::Array(array)) => {
+            let len_before = array.len();
+            array.retain(|elem| !predicate(elem));
+            array.len() != len_before
+        }
+        _ => false,
+    }
+}
+
+// Compare two values in MongoDB's sort order, where values of different types are ordered by type
+// (eg. null before numbers before strings) and numbers of different types are compared by value
+//
+pub fn compare_values(val1: &Bson, val2: &Bson) -> Ordering {
+    match (val1, val2) {
+        (Bson::String(str1), Bson::String(str2)) => str1.cmp(str2),
+        (Bson::Boolean(bool1), Bson::Boolean(bool2)) => bool1.cmp(bool2),
+        (Bson::DateTime(dt1), Bson::DateTime(dt2)) => dt1.cmp(dt2),
+        (Bson::ObjectId(oid1), Bson::ObjectId(oid2)) => oid1.bytes().cmp(&oid2.bytes()),
+        _ => match (as_number(val1), as_number(val2)) {
+            (Some(num1), Some(num2)) => num1.total_cmp(&num2),
+            _ => type_sort_rank(val1).cmp(&type_sort_rank(val2)),
+        },
+    }
+}
+
+// Get a numeric value as a double, if the value is a number
+//
+fn as_number(val: &Bson) -> Option<f64> {
+    match val {
+        Bson::Int32(num) => Some(f64::from(*num)),
+        Bson::Int64(num) => Some(*num as f64),
+        Bson::Double(num) => Some(*num),
+        _ => None,
+    }
+}
+
+// Position of a value's type in MongoDB's sort order for values of different types
+//
+fn type_sort_rank(val: &Bson) -> u8 {
+    match val {
+        Bson::MinKey => 0,
+        Bson::Null | Bson::Undefined => 1,
+        Bson::Int32(_) | Bson::Int64(_) | Bson::Double(_) | Bson::Decimal128(_) => 2,
+        Bson::String(_) | Bson::Symbol(_) => 3,
+        Bson::Document(_) => 4,
+        Bson::Array(_) => 5,
+        Bson::Binary(_) => 6,
+        Bson::ObjectId(_) => 7,
+        Bson::Boolean(_) => 8,
+        Bson::DateTime(_) => 9,
+        Bson::Timestamp(_) => 10,
+        Bson::RegularExpression(_) => 11,
+        _ => 12,
+    }
+}
+
+// Check if two documents clash on the unique '(title, author)' index
+//
+fn same_title_and_author(doc1: &Document, doc2: &Document) -> bool {
+    doc1.get("title") == doc2.get("title") && doc1.get("author") == doc2.get("author")
+}
+
+// Build the error MongoDB reports when a write clashes with the unique index on title & author
+//
+fn dup_key_error(doc: &Document) -> AppError {
+    AppError::DuplicateKey {
+        field: Some(String::from("title,author")),
+        message: format!(
+            "E11000 duplicate key error collection: library.books index: title_1_author_1 dup key: \
+            {{ title: {}, author: {} }}",
+            doc.get("title").unwrap_or(&Bson::Null),
+            doc.get("author").unwrap_or(&Bson::Null)
+        ),
+    }
+}
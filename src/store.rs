@@ -0,0 +1,227 @@
+use bson::{doc, oid::ObjectId, Bson, Document};
+use mongodb::options::{ClientOptions, ReadConcern, ReadConcernLevel, WriteConcern};
+use mongodb::results::{DeleteResult, UpdateResult};
+use mongodb::Client;
+use std::fmt;
+
+use crate::error::AppError;
+use crate::mem_store::{field_equals, MemCollection};
+
+// Where the shared book records are kept, being either a MongoDB collection or an in-process
+// in-memory store
+#[derive(Debug, Clone)]
+pub enum StoreLocation {
+    InMemory,
+    MongoDb { client_options: Box<ClientOptions>, db_name: String, coll_name: String },
+}
+
+impl fmt::Display for StoreLocation {
+    // Describe the location without the credentials which the MongoDB URL may contain
+    //
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InMemory => write!(f, "an in-memory store"),
+            Self::MongoDb { client_options, db_name, coll_name } => {
+                let hosts: Vec<String> =
+                    client_options.hosts.iter().map(|host| host.to_string()).collect();
+                write!(f, "MongoDB collection '{}.{}' at '{}'", db_name, coll_name, hosts.join(","))
+            }
+        }
+    }
+}
+
+impl StoreLocation {
+    // Connect to the shared book records, where the app name identifies the MongoDB client in the
+    // server's logs (unless the MongoDB URL specifies its own app name), returning an error if the
+    // MongoDB client can't be created from the client options
+    //
+    pub fn connect(&self, app_name: &str) -> Result<StoreConnection, AppError> {
+        Ok(match self {
+            Self::InMemory => StoreConnection::InMemory(MemCollection::default()),
+            Self::MongoDb { client_options, db_name, coll_name } => {
+                let mut client_options = client_options.as_ref().clone();
+                client_options.app_name.get_or_insert_with(|| app_name.to_string());
+                StoreConnection::MongoDb {
+                    client: Client::with_options(client_options)?,
+                    db_name: db_name.clone(),
+                    coll_name: coll_name.clone(),
+                }
+            }
+        })
+    }
+}
+
+// Connection to the shared book records, being a MongoDB client (with its pool of connections) or
+// the in-memory store, which apps running in the same process share
+#[derive(Debug, Clone)]
+pub enum StoreConnection {
+    InMemory(MemCollection),
+    MongoDb { client: Client, db_name: String, coll_name: String },
+}
+
+// Concerns of each kind of an app's operations, being reads of a single book, listings (including
+// searches & summaries) and writes, each defaulting to the MongoDB client's concern if not given,
+// and whether each operation runs in a causally consistent session
+#[derive(Debug, Clone, Default)]
+pub struct OperationSettings {
+    pub book_read_concern: Option<ReadConcern>,
+    pub listing_read_concern: Option<ReadConcern>,
+    pub write_concern: Option<WriteConcern>,
+    pub causal_consistency: bool,
+}
+
+impl OperationSettings {
+    // Whether reads of a single book run in a causally consistent session, which they can't if
+    // linearizable, as MongoDB doesn't support linearizable causal reads (though a linearizable
+    // read observes every write acknowledged by a majority anyway)
+    //
+    pub fn causal_book_reads(&self) -> bool {
+        let level = self.book_read_concern.as_ref().map(|concern| &concern.level);
+        self.causal_consistency && level != Some(&ReadConcernLevel::Linearizable)
+    }
+}
+
+// How a read or write identifies the book record it targets, either by the book's stable id or, for
+// compatibility with clients which predate ids, by its title & author, optionally only whilst the
+// record is still at one of the given versions (identified by the count of writes to the record)
+// so a write based on an out of date read is never applied
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BookKey {
+    ident: BookIdent,
+    versions: Option<Vec<i64>>,
+}
+
+// Identity of a book record, by id or by title & author
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum BookIdent {
+    Id(ObjectId),
+    TitleAuthor { title: String, author: String },
+}
+
+impl BookKey {
+    // Parse a book id provided in a request path, returning an error if it is not a valid id
+    //
+    pub fn from_id(id: &str) -> Result<Self, AppError> {
+        let id = ObjectId::parse_str(id).map_err(|_| AppError::InvalidField {
+            field: String::from("id"),
+            reason: String::from("must be a book id of 24 hexadecimal characters"),
+        })?;
+        Ok(Self { ident: BookIdent::Id(id), versions: None })
+    }
+
+    // Build key from a book's title & author, returning an error naming whichever is missing
+    //
+    pub fn from_title_author(
+        title: Option<&String>, author: Option<&String>,
+    ) -> Result<Self, AppError> {
+        let title = title.ok_or_else(|| AppError::MissingField(String::from("title")))?;
+        let author = author.ok_or_else(|| AppError::MissingField(String::from("author")))?;
+        let ident = BookIdent::TitleAuthor { title: title.clone(), author: author.clone() };
+        Ok(Self { ident, versions: None })
+    }
+
+    // Only target the book whilst it's at one of the versions, if any versions are provided
+    //
+    pub fn at_versions(self, versions: Option<Vec<i64>>) -> Self {
+        Self { versions, ..self }
+    }
+
+    // Same key but targeting the book at any version
+    //
+    pub fn any_version(&self) -> Self {
+        Self { ident: self.ident.clone(), versions: None }
+    }
+
+    // Whether the book is only targeted whilst at particular versions
+    //
+    pub fn has_versions(&self) -> bool {
+        self.versions.is_some()
+    }
+
+    // Check whether the book at the given version is targeted, where a book which has no version
+    // never matches particular versions
+    //
+    pub fn matches_version(&self, version: Option<i64>) -> bool {
+        match &self.versions {
+            Some(versions) => version.is_some_and(|version| versions.contains(&version)),
+            None => true,
+        }
+    }
+
+    // Build the query filter matching the targeted book
+    //
+    pub fn filter(&self) -> Document {
+        let mut filter_doc = match &self.ident {
+            BookIdent::Id(id) => doc! {"_id": id},
+            BookIdent::TitleAuthor { title, author } => doc! {"title": title, "author": author},
+        };
+
+        if let Some(versions) = &self.versions {
+            filter_doc.insert("version", doc! {"$in": versions.clone()});
+        }
+
+        filter_doc
+    }
+
+    // Check whether a record is the targeted book, emulating the query filter
+    //
+    pub fn matches(&self, doc: &Document) -> bool {
+        let is_book = match &self.ident {
+            BookIdent::Id(id) => doc.get("_id") == Some(&Bson::ObjectId(*id)),
+            BookIdent::TitleAuthor { title, author } => {
+                field_equals(doc, "title", title) && field_equals(doc, "author", author)
+            }
+        };
+        is_book && self.matches_version(record_version(doc))
+    }
+}
+
+// Read the version of a record, being the count of writes to it (incremented by every write of
+// either app), whether stored as a 32 or 64-bit integer
+//
+fn record_version(doc: &Document) -> Option<i64> {
+    match doc.get("version") {
+        Some(Bson::Int32(version)) => Some(i64::from(*version)),
+        Some(Bson::Int64(version)) => Some(*version),
+        _ => None,
+    }
+}
+
+impl fmt::Display for BookKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.ident {
+            BookIdent::Id(id) => write!(f, "with id `{}`", id),
+            BookIdent::TitleAuthor { title, author } => {
+                write!(f, "with title `{}` and author `{}`", title, author)
+            }
+        }
+    }
+}
+
+// Number of records a write operation matched & changed, reported by both the MongoDB and the
+// in-memory storage backends so the REST API can tell when a targeted book does not exist
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WriteCounts {
+    pub matched_count: u64,
+    pub modified_count: u64,
+}
+
+impl WriteCounts {
+    // Whether the write found no record to act upon
+    //
+    pub fn is_unmatched(&self) -> bool {
+        self.matched_count == 0
+    }
+}
+
+impl From<UpdateResult> for WriteCounts {
+    fn from(result: UpdateResult) -> Self {
+        Self { matched_count: result.matched_count, modified_count: result.modified_count }
+    }
+}
+
+impl From<DeleteResult> for WriteCounts {
+    fn from(result: DeleteResult) -> Self {
+        Self { matched_count: result.deleted_count, modified_count: result.deleted_count }
+    }
+}
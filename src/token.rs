@@ -0,0 +1,31 @@
+use bson::Document;
+use std::fmt::Write;
+
+use crate::error::AppError;
+
+// Encode a document as an opaque token for a client to pass back on a later request, being the
+// document's BSON bytes in hexadecimal
+//
+pub fn encode_token(doc: &Document) -> Result<String, AppError> {
+    let mut bytes = vec![];
+    doc.to_writer(&mut bytes).map_err(|e| AppError::Internal(e.to_string()))?;
+    Ok(bytes.iter().fold(String::with_capacity(bytes.len() * 2), |mut token, byte| {
+        let _ = write!(token, "{:02x}", byte);
+        token
+    }))
+}
+
+// Decode a token previously provided to a client back to its document, returning nothing if the
+// token is not valid hexadecimal BSON
+//
+pub fn decode_token(token: &str) -> Option<Document> {
+    if !token.is_ascii() || !token.len().is_multiple_of(2) {
+        return None;
+    }
+
+    let bytes = (0..token.len())
+        .step_by(2)
+        .map(|pos| u8::from_str_radix(&token[pos..pos + 2], 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    Document::from_reader(&mut bytes.as_slice()).ok()
+}
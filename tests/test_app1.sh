@@ -30,6 +30,136 @@ else
     printf "====OK: New book exists\n"
 fi
 
+printf "\nTest new book found by filters HTTP GET result:\n"
+if curl -sS --location --request GET "${URL}?author_prefix=bad%20wri&ignore_case=true&year_gte=2020&year_lt=2021&quantity_gte=3" | grep "Bad Book"; then
+    printf "====OK: New book found by author prefix, year range & quantity threshold\n"
+else
+    printf "====ERROR: New book not found by author prefix, year range & quantity threshold\n"
+    exit 1
+fi
+
+printf "\nTest new book found by text search HTTP GET result:\n"
+if curl -sS --location --request GET "${URL}/search?q=bad%20writer" | grep '"title":"Bad Book"'; then
+    printf "====OK: New book found by searching for its author\n"
+else
+    printf "====ERROR: New book not found by searching for its author\n"
+    exit 1
+fi
+
+printf "\nTest new book listed first when sorted by descending year HTTP GET result:\n"
+if curl -sS --location --request GET "${URL}?sort=-year,title&limit=1" | grep '"title":"Bad Book"'; then
+    printf "====OK: Newest book listed first\n"
+else
+    printf "====ERROR: Newest book not listed first\n"
+    exit 1
+fi
+
+printf "\nTest new book page with total count HTTP GET result:\n"
+if curl -sS --location --request GET "${URL}?title=Bad%20Book&author=Bad%20Writer&limit=1&count=true" | grep '"next":null,"total":1}$'; then
+    printf "====OK: Single page containing the one new book\n"
+else
+    printf "====ERROR: Page of books does not contain just the one new book\n"
+    exit 1
+fi
+
+printf "\nTest forged cursor with a query operator for its year value HTTP GET result:\n"
+FORGED_CURSOR='4700000002736f7274000500000079656172000476616c756573001b000000033000130000000224726567657800020000002e0000000769640062a0c0ffee0000000000beef00'
+if curl -sS --location --request GET "${URL}?limit=1&cursor=${FORGED_CURSOR}" | grep '"code":"invalid_field".*"field":"cursor"'; then
+    printf "====OK: Forged cursor rejected\n"
+else
+    printf "====ERROR: Forged cursor not rejected\n"
+    exit 1
+fi
+
+printf "\nTest new book found by its creation timestamp HTTP GET result:\n"
+CREATED=$(curl -sS --location --request GET "${URL}?title=Bad%20Book&author=Bad%20Writer&fields=first_created" | sed -n 's/^\[{"id":"[0-9a-f]*","first_created":"\([^"]*\)"}\]$/\1/p')
+if [ -n "${CREATED}" ] && curl -sS --location --request GET "${URL}?first_created_since=${CREATED}&last_modified_since=${CREATED}" | grep "Bad Book" \
+        && ! curl -sS --location --request GET "${URL}?first_created_before=${CREATED}" | grep "Bad Book"; then
+    printf "====OK: New book found by RFC 3339 creation & modification timestamps\n"
+else
+    printf "====ERROR: New book not found by RFC 3339 creation & modification timestamps\n"
+    exit 1
+fi
+
+printf "\nTest new book narrowed to selected fields HTTP GET result:\n"
+if curl -sS --location --request GET "${URL}?title=Bad%20Book&author=Bad%20Writer&fields=title,quantity" | grep '^\[{"id":"[0-9a-f]\{24\}","title":"Bad Book","quantity":3}\]$'; then
+    printf "====OK: Only the selected fields of the new book returned\n"
+else
+    printf "====ERROR: Fields of the new book returned are not just the selected fields\n"
+    exit 1
+fi
+
+printf "\nTest unchanged book list not resent HTTP GET result:\n"
+ETAG=$(curl -sS -D - -o /dev/null --location --request GET "${URL}?author=Bad%20Writer" | tr -d '\r' | sed -n 's/^etag: //Ip')
+STATUS=$(curl -sS -o /dev/null -w "%{http_code}" --location --request GET "${URL}?author=Bad%20Writer" --header "If-None-Match: ${ETAG}")
+if [ -n "${ETAG}" ] && [ "${STATUS}" = "304" ]; then
+    printf "====OK: Unchanged book list not modified since the ETag\n"
+else
+    printf "====ERROR: Read of unchanged book list returned HTTP status ${STATUS} rather than 304\n"
+    exit 1
+fi
+
+printf "\nTest new book streamed as NDJSON HTTP GET result:\n"
+if curl -sS --location --request GET "${URL}?title=Bad%20Book&author=Bad%20Writer&stream=ndjson" | grep '^{"id":"[0-9a-f]\{24\}","title":"Bad Book","author":"Bad Writer","year":2020,"quantity":3,"explicit":null,"first_created":"[^"]*","last_modified":"[^"]*"}$'; then
+    printf "====OK: New book streamed on its own line\n"
+else
+    printf "====ERROR: New book not streamed on its own line\n"
+    exit 1
+fi
+
+printf "\nTest new book read by its id HTTP GET result:\n"
+ID=$(curl -sS --location --request GET "${URL}?title=Bad%20Book&author=Bad%20Writer&fields=title" | sed -n 's/^\[{"id":"\([0-9a-f]*\)","title":"Bad Book"}\]$/\1/p')
+if [ -n "${ID}" ] && curl -sS --location --request GET "${URL}/${ID}" | grep '"title":"Bad Book"'; then
+    printf "====OK: New book read by its id\n"
+else
+    printf "====ERROR: New book not read by its id\n"
+    exit 1
+fi
+
+printf "\nChange new book year by its id HTTP PATCH output:\n"
+curl -sS --location --request PATCH "${URL}/${ID}" \
+--header 'Content-Type: application/json' \
+--data-raw '{
+    "year": 2021
+}'
+
+printf "\n\nTest new book year changed HTTP GET result:\n"
+if curl -sS --location --request GET "${URL}/${ID}?fields=year,quantity" | grep '^{"id":"'"${ID}"'","year":2021,"quantity":3}$'; then
+    printf "====OK: Only the new book's year changed\n"
+else
+    printf "====ERROR: The new book's year not changed, or other fields changed\n"
+    exit 1
+fi
+
+printf "\nTest change of new book with out of date ETag HTTP PATCH result:\n"
+ETAG=$(curl -sS -D - -o /dev/null --location --request GET "${URL}/${ID}" | tr -d '\r' | sed -n 's/^etag: //Ip')
+STATUS=$(curl -sS -o /dev/null -w "%{http_code}" --location --request PATCH "${URL}/${ID}" \
+--header 'Content-Type: application/json' \
+--header 'If-Match: "0"' \
+--data-raw '{
+    "year": 2022
+}')
+if [ -n "${ETAG}" ] && [ "${STATUS}" = "412" ]; then
+    printf "====OK: Change of new book with out of date ETag rejected\n"
+else
+    printf "====ERROR: Change of new book with out of date ETag returned HTTP status ${STATUS} rather than 412\n"
+    exit 1
+fi
+
+printf "\nTest change of new book with current ETag HTTP PATCH result:\n"
+STATUS=$(curl -sS -o /dev/null -w "%{http_code}" --location --request PATCH "${URL}/${ID}" \
+--header 'Content-Type: application/json' \
+--header "If-Match: ${ETAG}" \
+--data-raw '{
+    "year": 2021
+}')
+if [ "${STATUS}" = "200" ]; then
+    printf "====OK: Change of new book with current ETag applied\n"
+else
+    printf "====ERROR: Change of new book with current ETag returned HTTP status ${STATUS} rather than 200\n"
+    exit 1
+fi
+
 
 printf "\nUpdate new book quantity HTTP PUT output:\n"
 curl -sS --location --request PUT "${URL}" \
@@ -48,6 +178,7 @@ else
     printf "====OK: New book exists\n"
 fi
 
+LIST_ETAG=$(curl -sS -D - -o /dev/null --location --request GET "${URL}" | tr -d '\r' | sed -n 's/^etag: //Ip')
 
 printf "\nDelete new book HTTP DELETE output: \n"
 curl --location --request DELETE "${URL}" \
@@ -66,5 +197,39 @@ else
     exit 1
 fi
 
+printf "\nTest book list resent after a book removed HTTP GET result:\n"
+HEADERS=$(curl -sS -D - -o /dev/null --location --request GET "${URL}" --header "If-None-Match: ${LIST_ETAG}" | tr -d '\r')
+if [ -n "${LIST_ETAG}" ] && echo "${HEADERS}" | grep -q '^HTTP/1.1 200'; then
+    printf "====OK: Book list modified since the ETag\n"
+else
+    printf "====ERROR: Book list not resent in full\n"
+    exit 1
+fi
+
+
+printf "\nTest removed book not found by its id HTTP GET result:\n"
+STATUS=$(curl -sS -o /dev/null -w "%{http_code}" --location --request GET "${URL}/${ID}")
+if [ "${STATUS}" = "404" ]; then
+    printf "====OK: Removed book not found by its id\n"
+else
+    printf "====ERROR: Read of removed book by its id returned HTTP status ${STATUS} rather than 404\n"
+    exit 1
+fi
+
+printf "\nTest update of removed book quantity HTTP PUT result:\n"
+STATUS=$(curl -sS -o /dev/null -w "%{http_code}" --location --request PUT "${URL}" \
+--header 'Content-Type: application/json' \
+--data-raw '{
+    "title": "Bad Book",
+    "author": "Bad Writer",
+    "quantity": 5
+}')
+if [ "${STATUS}" = "404" ]; then
+    printf "====OK: Removed book not found for update\n"
+else
+    printf "====ERROR: Update of removed book returned HTTP status ${STATUS} rather than 404\n"
+    exit 1
+fi
+
 printf "\n"
 
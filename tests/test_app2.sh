@@ -1,5 +1,6 @@
 #!/bin/bash
 URL='localhost:8282/v1/books'
+URL_V2='localhost:8282/v2/books'
 
 printf "\nInitial HTTP GET output:\n"
 curl -sS --location --request GET "${URL}?title=The%20Day%20of%20the%20Triffids&author=John%20Wyndham"
@@ -40,6 +41,133 @@ else
     exit 1
 fi
 
+printf "\nTest score summaries for all books by author HTTP GET result:\n"
+if curl -sS --location --request GET "${URL}?author=John%20Wyndham&sort=-rating" | grep '"title":"The Day of the Triffids","author":"John Wyndham","year":1951,"average_score":9.5,"scores_counted":2'; then
+    printf "====OK: Book listed with correct average score of 9.5 & count of 2 scores\n"
+else
+    printf "====ERROR: The book is not listed with an average score of 9.5 & count of 2 scores\n"
+    exit 1
+fi
+
+printf "\nTest score summaries narrowed to selected fields HTTP GET result:\n"
+if curl -sS --location --request GET "${URL}?author=John%20Wyndham&fields=title,scores" | grep '{"id":"[0-9a-f]\{24\}","title":"The Day of the Triffids","average_score":9.5,"scores_counted":2}'; then
+    printf "====OK: Only the title & score fields of the book listed\n"
+else
+    printf "====ERROR: Fields of the book listed are not just the title & score fields\n"
+    exit 1
+fi
+
+printf "\nTest individual scores listed for book HTTP GET API v2 result:\n"
+if curl -sS --location --request GET "${URL_V2}?title=The%20Day%20of%20the%20Triffids&author=John%20Wyndham" | grep '"reference":"The Paperback Store","rating":9.0'; then
+    printf "====OK: Individual scores listed for the book\n"
+else
+    printf "====ERROR: The individual scores for the book are not listed\n"
+    exit 1
+fi
+
+printf "\nTest time of last modification of book HTTP GET API v2 result:\n"
+if curl -sS --location --request GET "${URL_V2}?title=The%20Day%20of%20the%20Triffids&author=John%20Wyndham&fields=last_modified" | grep '^{"id":"[0-9a-f]\{24\}","last_modified":"[0-9]\{4\}-[0-9]\{2\}-[0-9]\{2\}T[0-9:.]*Z"}$'; then
+    printf "====OK: Time of last modification of the book returned as an RFC 3339 timestamp\n"
+else
+    printf "====ERROR: Time of last modification of the book not returned as an RFC 3339 timestamp\n"
+    exit 1
+fi
+
+printf "\nTest unchanged book scores not resent HTTP GET result:\n"
+MODIFIED=$(curl -sS -D - -o /dev/null --location --request GET "${URL}?title=The%20Day%20of%20the%20Triffids&author=John%20Wyndham" | tr -d '\r' | sed -n 's/^last-modified: //Ip')
+STATUS=$(curl -sS -o /dev/null -w "%{http_code}" --location --request GET "${URL}?title=The%20Day%20of%20the%20Triffids&author=John%20Wyndham" --header "If-Modified-Since: ${MODIFIED}")
+if [ -n "${MODIFIED}" ] && [ "${STATUS}" = "304" ]; then
+    printf "====OK: Unchanged book scores not modified since the time last read\n"
+else
+    printf "====ERROR: Read of unchanged book scores returned HTTP status ${STATUS} rather than 304\n"
+    exit 1
+fi
+
+printf "\nTest individual scores for book sorted by rating HTTP GET result:\n"
+if curl -sS --location --request GET "${URL}/scores?title=The%20Day%20of%20the%20Triffids&author=John%20Wyndham&sort=rating" | grep '^\[{"reference":"The Paperback Store","rating":9.0},{"reference":"The Science Fiction Reviewer","rating":10.0}\]$'; then
+    printf "====OK: Individual scores listed in ascending rating order\n"
+else
+    printf "====ERROR: The individual scores for the book are not listed in ascending rating order\n"
+    exit 1
+fi
+
+printf "\nTest individual scores for book filtered by reference HTTP GET result:\n"
+if curl -sS --location --request GET "${URL}/scores?title=The%20Day%20of%20the%20Triffids&author=John%20Wyndham&reference=The%20Paperback%20Store" | grep '^\[{"reference":"The Paperback Store","rating":9.0}\]$'; then
+    printf "====OK: Only the individual score from the reference listed\n"
+else
+    printf "====ERROR: The individual scores listed are not just the one from the reference\n"
+    exit 1
+fi
+
+printf "\nTest individual scores read by book id HTTP GET API v2 result:\n"
+ID=$(curl -sS --location --request GET "${URL}?title=The%20Day%20of%20the%20Triffids&author=John%20Wyndham&fields=title" | sed -n 's/^{"id":"\([0-9a-f]*\)","title":"The Day of the Triffids"}$/\1/p')
+if [ -n "${ID}" ] && curl -sS --location --request GET "${URL_V2}/${ID}" | grep '"reference":"The Paperback Store","rating":9.0'; then
+    printf "====OK: Individual scores read by the book's id\n"
+else
+    printf "====ERROR: The individual scores for the book are not read by its id\n"
+    exit 1
+fi
+
+printf "\nTest change of score from unknown reviewer by book id HTTP PATCH result:\n"
+STATUS=$(curl -sS -o /dev/null -w "%{http_code}" --location --request PATCH "${URL}/${ID}" \
+--header 'Content-Type: application/json' \
+--data-raw '{
+    "reference": "The Unknown Reviewer",
+    "score": 5
+}')
+if [ "${STATUS}" = "404" ]; then
+    printf "====OK: Change of score from unknown reviewer not found\n"
+else
+    printf "====ERROR: Change of score from unknown reviewer returned HTTP status ${STATUS} rather than 404\n"
+    exit 1
+fi
+
+printf "\nTest out of range score for a book HTTP POST result:\n"
+STATUS=$(curl -sS -o /dev/null -w "%{http_code}" --location --request POST "${URL}" \
+--header 'Content-Type: application/json' \
+--data-raw '{
+    "title": "The Day of the Triffids",
+    "author": "John Wyndham",
+    "reference": "The Overenthusiastic Reviewer",
+    "score": 11
+}')
+if [ "${STATUS}" = "400" ]; then
+    printf "====OK: Out of range score rejected\n"
+else
+    printf "====ERROR: Out of range score returned HTTP status ${STATUS} rather than 400\n"
+    exit 1
+fi
+
+printf "\nTest missing score for a book HTTP POST result:\n"
+if curl -sS --location --request POST "${URL}" \
+--header 'Content-Type: application/json' \
+--data-raw '{
+    "title": "The Day of the Triffids",
+    "author": "John Wyndham",
+    "reference": "The Forgetful Reviewer"
+}' | grep '"code":"missing_field".*"field":"score"'; then
+    printf "====OK: Missing score rejected naming the score field\n"
+else
+    printf "====ERROR: Missing score not rejected naming the score field\n"
+    exit 1
+fi
+
+printf "\nTest second score from same reviewer for a book HTTP POST result:\n"
+STATUS=$(curl -sS -o /dev/null -w "%{http_code}" --location --request POST "${URL}" \
+--header 'Content-Type: application/json' \
+--data-raw '{
+    "title": "The Day of the Triffids",
+    "author": "John Wyndham",
+    "reference": "The Paperback Store",
+    "score": 1
+}')
+if [ "${STATUS}" = "409" ]; then
+    printf "====OK: Duplicate score from same reviewer rejected\n"
+else
+    printf "====ERROR: Duplicate score from same reviewer returned HTTP status ${STATUS} rather than 409\n"
+    exit 1
+fi
+
 
 printf "\nDelete a specific score by reference for a book: \n"
 curl --location --request DELETE "${URL}" \
@@ -68,15 +196,51 @@ else
     exit 1
 fi
 
+printf "\nConcurrently update the same score for a book many times: \n"
+for i in $(seq 1 10); do
+    curl -sS -o /dev/null --location --request PUT "${URL}" \
+    --header 'Content-Type: application/json' \
+    --data-raw '{
+        "title": "The Day of the Triffids",
+        "author": "John Wyndham",
+        "reference": "The Science Fiction Reviewer",
+        "score": 10
+    }' &
+done
+wait
+
+printf "\nTest concurrent updates left exactly one score per reviewer HTTP GET result:\n"
+if curl -sS --location --request GET "${URL}?title=The%20Day%20of%20the%20Triffids&author=John%20Wyndham" | grep '"score":9.0'; then
+    printf "====OK: Correct average score of 9.0 for the book, so no duplicate scores\n"
+else
+    printf "====ERROR: The average score for the book should be 9 but it is not, so duplicate scores exist\n"
+    exit 1
+fi
+
 printf "\nDelete a specific score by reference for a book: \n"
 curl --location --request DELETE "${URL}" \
 --header 'Content-Type: application/json' \
 --data-raw '{
     "title": "The Day of the Triffids",
     "author": "John Wyndham",
-    "reference": "The Paperback Store"
+    "reference": "The Science Fiction Reviewer"
 }'
 
+printf "\n\nTest delete of score for a book id with out of date ETag HTTP DELETE result:\n"
+ETAG=$(curl -sS -D - -o /dev/null --location --request GET "${URL_V2}/${ID}" | tr -d '\r' | sed -n 's/^etag: //Ip')
+STATUS=$(curl -sS -o /dev/null -w "%{http_code}" --location --request DELETE "${URL}/${ID}?reference=The%20Paperback%20Store" \
+--header 'If-Match: "0"')
+if [ -n "${ETAG}" ] && [ "${STATUS}" = "412" ]; then
+    printf "====OK: Delete of score with out of date ETag rejected\n"
+else
+    printf "====ERROR: Delete of score with out of date ETag returned HTTP status ${STATUS} rather than 412\n"
+    exit 1
+fi
+
+printf "\nDelete a specific score by reference for a book id with current ETag: \n"
+curl --location --request DELETE "${URL}/${ID}?reference=The%20Paperback%20Store" \
+--header "If-Match: ${ETAG}"
+
 printf "\n\nTest no scores exist for the book HTTP GET result:\n"
 if curl -sS --location --request GET "${URL}?title=The%20Day%20of%20the%20Triffids&author=John%20Wyndham" | grep "No scores recorded"; then
     printf "====OK: No scores for the book exist\n"